@@ -1,22 +1,35 @@
+use crate::world::grid::GridCoord;
 use rapier3d::prelude::*;
-use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 
 // Player component
+#[derive(Serialize, Deserialize)]
 pub struct Player {}
 
 // Tree component
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Tree {
     pub leaf_width: f32,
     pub leaf_height: f32,
     pub trunk_height: f32,
-    pub leaf_color: Color,
-    pub trunk_color: Color,
+    pub leaf_color: Rgba,
+    pub trunk_color: Rgba,
 }
 
 // Ball component
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Ball {
     pub size: f32,
-    pub color: Color,
+    pub color: Rgba,
+}
+
+// Item component
+// Picked up by the player for a Collect objective
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Item {
+    pub size: f32,
+    pub color: Rgba,
 }
 
 // Witch component
@@ -24,16 +37,25 @@ pub struct Witch {
     pub width: f32,
     pub height: f32,
     pub collider_handle: ColliderHandle,
-    pub color: Color,
+    pub color: Rgba,
     pub state: WitchState,
-    pub target: Vector3,
-}
-
-// Block component
-pub struct Block {
-    pub width: f32,
-    pub height: f32,
-    pub color: Color,
+    // Seconds spent in the current state
+    pub state_time: f32,
+    pub target: Vector<f32>,
+    // Index of the map's patrol route the witch follows, random patrol points if not set
+    pub route: Option<usize>,
+    // Next waypoint along her patrol route
+    pub waypoint: usize,
+    // Where the witch spawned and returns to after a search
+    pub home: Vector<f32>,
+    // Direction the witch is looking along the ground
+    pub facing: Vector<f32>,
+    // Where the player was last seen or heard
+    pub last_known_position: Option<Vector<f32>>,
+    // Set when no path could be found to the current goal
+    pub goal_unreachable: bool,
+    pub path: VecDeque<GridCoord>,
+    pub path_goal: Option<GridCoord>,
 }
 
 // Body Handle component
@@ -42,7 +64,43 @@ pub struct BodyHandle {
 }
 
 // Witch behavior state
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum WitchState {
-    Patrolling,
-    Chasing,
+    Idle,
+    Patrol,
+    Suspicious,
+    Investigate,
+    Chase,
+    Search,
+    Return,
+}
+
+// RGBA color, kept separate from the renderer so the simulation runs headless
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+// RGBA color functions
+impl Rgba {
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    // Colors used by entities (same values as Raylib's)
+    pub const GREEN: Rgba = Rgba::new(0, 128, 0, 255);
+    pub const DARKGREEN: Rgba = Rgba::new(0, 100, 0, 255);
+    pub const LIMEGREEN: Rgba = Rgba::new(50, 205, 50, 255);
+    pub const BROWN: Rgba = Rgba::new(165, 42, 42, 255);
+    pub const DARKBROWN: Rgba = Rgba::new(76, 63, 47, 255);
+    pub const GRAY: Rgba = Rgba::new(128, 128, 128, 255);
+    pub const DARKGRAY: Rgba = Rgba::new(169, 169, 169, 255);
+    pub const BLUE: Rgba = Rgba::new(0, 0, 255, 255);
+    pub const PURPLE: Rgba = Rgba::new(128, 0, 128, 255);
+    pub const RED: Rgba = Rgba::new(230, 41, 55, 255);
+    pub const GOLD: Rgba = Rgba::new(255, 203, 0, 255);
+    pub const ORANGE: Rgba = Rgba::new(255, 161, 0, 255);
 }
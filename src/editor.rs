@@ -0,0 +1,594 @@
+use crate::input::actions::*;
+use crate::input::bindings::*;
+use crate::pause::*;
+use crate::settings::*;
+use crate::systems::player::*;
+use crate::systems::terrain_mesh::*;
+use crate::world::grid::*;
+use crate::world::loader::*;
+use crate::world::patrol::*;
+use crate::world::spawns::*;
+use crate::world::writer::*;
+use crate::{State, Transition};
+use raylib::prelude::*;
+
+// Width and depth of maps started from scratch
+const NEW_MAP_SIZE: usize = 30;
+
+// Camera speeds when flying and flying fast
+const FLY_SPEED: f32 = 8.0;
+const FAST_FLY_SPEED: f32 = 20.0;
+
+// How far away the cursor can be from the camera
+const CURSOR_REACH: f32 = 100.0;
+
+// Keys that pick tools, in the same order as EditorTool::ALL
+const TOOL_KEYS: [KeyboardKey; 9] = [
+    KeyboardKey::KEY_ONE,
+    KeyboardKey::KEY_TWO,
+    KeyboardKey::KEY_THREE,
+    KeyboardKey::KEY_FOUR,
+    KeyboardKey::KEY_FIVE,
+    KeyboardKey::KEY_SIX,
+    KeyboardKey::KEY_SEVEN,
+    KeyboardKey::KEY_EIGHT,
+    KeyboardKey::KEY_NINE,
+];
+
+// Editor tool
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EditorTool {
+    Grass,
+    Stone,
+    Exit,
+    PlayerStart,
+    Witch,
+    Tree,
+    Ball,
+    Item,
+    PatrolRoute,
+}
+
+// Functions for EditorTool
+impl EditorTool {
+    // All tools
+    pub const ALL: [EditorTool; 9] = [
+        EditorTool::Grass,
+        EditorTool::Stone,
+        EditorTool::Exit,
+        EditorTool::PlayerStart,
+        EditorTool::Witch,
+        EditorTool::Tree,
+        EditorTool::Ball,
+        EditorTool::Item,
+        EditorTool::PatrolRoute,
+    ];
+
+    // Get name of tool
+    pub fn name(self) -> &'static str {
+        match self {
+            EditorTool::Grass => "Grass",
+            EditorTool::Stone => "Stone",
+            EditorTool::Exit => "Exit",
+            EditorTool::PlayerStart => "Player start",
+            EditorTool::Witch => "Witch",
+            EditorTool::Tree => "Tree",
+            EditorTool::Ball => "Ball",
+            EditorTool::Item => "Item",
+            EditorTool::PatrolRoute => "Patrol route",
+        }
+    }
+
+    // Get tile painted by tool, if it paints tiles
+    fn tile_type(self) -> Option<TileType> {
+        match self {
+            EditorTool::Grass => Some(TileType::Grass),
+            EditorTool::Stone => Some(TileType::Stone),
+            EditorTool::Exit => Some(TileType::Exit),
+            _ => None,
+        }
+    }
+}
+
+// Editor
+// Free camera over a map's grid for painting tiles and placing spawns and patrol routes
+pub struct Editor {
+    pub filename: String,
+    pub map: LoadedMap,
+    pub camera: Camera3D,
+    pub mouse_look: MouseLook,
+    pub input_map: InputMap,
+    pub tool: EditorTool,
+    pub level: usize,
+    pub cursor: Option<GridCoord>,
+    // Points of the patrol route being drawn
+    pub route: Vec<GridCoord>,
+    // Result of the last save
+    pub status: Option<String>,
+    // Edited since the last save
+    pub dirty: bool,
+    pub terrain: Option<TerrainModels>,
+}
+
+// Functions for Editor
+impl Editor {
+    // Open a map in the maps directory
+    pub fn open(settings: &Settings, filename: &str) -> Result<Self, MapError> {
+        let map = load_map(filename)?;
+
+        Ok(Self::new(settings, filename, map))
+    }
+
+    // Start a new map with flat grass ground
+    pub fn new_map(settings: &Settings, filename: &str) -> Self {
+        let mut grid = Grid::new(NEW_MAP_SIZE, NEW_MAP_SIZE, 1);
+
+        for z in 0..NEW_MAP_SIZE {
+            for x in 0..NEW_MAP_SIZE {
+                grid.set(Tile {
+                    kind: TileType::Grass,
+                    coord: GridCoord { x, y: 0, z },
+                });
+            }
+        }
+
+        let map = LoadedMap {
+            grid,
+            objectives: Vec::new(),
+            patrol_routes: Vec::new(),
+            spawn_points: SpawnPoints::default(),
+        };
+
+        Self::new(settings, filename, map)
+    }
+
+    // Create editor for a map
+    fn new(settings: &Settings, filename: &str, map: LoadedMap) -> Self {
+        let tile_size = map.grid.tile_size;
+
+        // Start over the south edge of the map, looking north and down at it
+        let mut mouse_look = MouseLook::new(settings.mouse_sensitivity);
+        mouse_look.yaw = std::f32::consts::FRAC_PI_2;
+        mouse_look.pitch = -0.6;
+
+        let position = Vector3::new(
+            map.grid.width as f32 * tile_size / 2.0,
+            12.0 * tile_size,
+            -8.0 * tile_size,
+        );
+        let forward = build_camera_forward(mouse_look.yaw(), mouse_look.pitch());
+
+        let camera = Camera3D::perspective(
+            position,
+            position + Vector3::new(forward.x, forward.y, forward.z),
+            Vector3::new(0.0, 1.0, 0.0),
+            60.0,
+        );
+
+        Self {
+            filename: filename.to_string(),
+            map,
+            camera,
+            mouse_look,
+            input_map: InputMap::load_or_default(&settings.input_config),
+            tool: EditorTool::Grass,
+            level: 1,
+            cursor: None,
+            route: Vec::new(),
+            status: None,
+            dirty: false,
+            terrain: None,
+        }
+    }
+
+    // Update
+    pub fn update(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        transition: &mut Option<Transition>,
+    ) {
+        // If cursor is showing then disable it
+        if !rl.is_cursor_hidden() {
+            rl.disable_cursor();
+        }
+
+        // Escape goes back to the title screen, asking first if there are unsaved changes
+        if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+            *transition = Some(if self.dirty {
+                Transition::Push(State::Confirm(ConfirmDialog::new(
+                    ConfirmAction::DiscardEdits,
+                )))
+            } else {
+                Transition::Switch(State::TitleScreen)
+            });
+            return;
+        }
+
+        // Bake terrain meshes on the first update, then rebake chunks as tiles are painted
+        match &mut self.terrain {
+            Some(terrain) => terrain.rebuild_changed(rl, thread, &self.map.grid),
+            None => self.terrain = Some(TerrainModels::build(rl, thread, &self.map.grid)),
+        }
+
+        // Fly camera with the game's movement and look bindings
+        let actions = self.input_map.read(rl);
+        self.fly(
+            &actions,
+            rl.get_frame_time(),
+            rl.is_key_down(KeyboardKey::KEY_C),
+        );
+
+        // Pick tool
+        for (key, tool) in TOOL_KEYS.into_iter().zip(EditorTool::ALL) {
+            if rl.is_key_pressed(key) {
+                self.tool = tool;
+            }
+        }
+
+        // Change the level being edited with the mouse wheel, or R and F
+        // Up to one above the top level, so a stray click can't add a stack of empty levels
+        let wheel = rl.get_mouse_wheel_move();
+        if wheel > 0.0 || rl.is_key_pressed(KeyboardKey::KEY_R) {
+            self.level = (self.level + 1).min(self.map.grid.levels);
+        }
+        if wheel < 0.0 || rl.is_key_pressed(KeyboardKey::KEY_F) {
+            self.level = self.level.saturating_sub(1);
+        }
+
+        // Find the tile being pointed at on the current level
+        self.cursor = self.cursor_coord();
+
+        // Left click places, right click removes
+        if let Some(coord) = self.cursor {
+            if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+                self.place(coord);
+            }
+            if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT) {
+                self.remove(coord);
+            }
+        }
+
+        // Finish the patrol route being drawn, L closes it into a loop
+        if rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+            self.finish_route(false);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_L) {
+            self.finish_route(true);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
+            self.route.clear();
+        }
+
+        // Save with Ctrl+S
+        if rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL) && rl.is_key_pressed(KeyboardKey::KEY_S) {
+            self.save();
+        }
+    }
+
+    // Render
+    pub fn render(&self, d: &mut RaylibDrawHandle) {
+        let grid = &self.map.grid;
+        let tile_size = grid.tile_size;
+
+        // Clear frame
+        d.clear_background(Color::SKYBLUE);
+
+        // Draw 3D objects
+        d.draw_mode3D(self.camera, |mut d3d, _camera| {
+            // Draw terrain
+            if let Some(terrain) = &self.terrain {
+                terrain.draw(&mut d3d);
+            }
+
+            // Draw outline of the level being edited
+            let y = self.level as f32 * tile_size - tile_size / 2.0;
+            let min_x = -tile_size / 2.0;
+            let min_z = -tile_size / 2.0;
+            let max_x = grid.width as f32 * tile_size - tile_size / 2.0;
+            let max_z = grid.height as f32 * tile_size - tile_size / 2.0;
+            let corners = [
+                Vector3::new(min_x, y, min_z),
+                Vector3::new(max_x, y, min_z),
+                Vector3::new(max_x, y, max_z),
+                Vector3::new(min_x, y, max_z),
+            ];
+            for (index, corner) in corners.iter().enumerate() {
+                d3d.draw_line_3D(*corner, corners[(index + 1) % corners.len()], Color::BLUE);
+            }
+
+            // Draw spawn points
+            let spawns = &self.map.spawn_points;
+            if let Some(coord) = spawns.player {
+                d3d.draw_sphere(self.marker_position(coord), 0.4, Color::BLUE);
+            }
+            for coord in &spawns.witches {
+                d3d.draw_sphere(self.marker_position(*coord), 0.4, Color::PURPLE);
+            }
+            for tree in &spawns.trees {
+                d3d.draw_sphere(self.marker_position(tree.coord), 0.4, Color::DARKGREEN);
+            }
+            for coord in &spawns.balls {
+                d3d.draw_sphere(self.marker_position(*coord), 0.4, Color::RED);
+            }
+            for coord in &spawns.items {
+                d3d.draw_sphere(self.marker_position(*coord), 0.4, Color::ORANGE);
+            }
+
+            // Draw patrol routes, and the one being drawn
+            for route in &self.map.patrol_routes {
+                self.draw_route(&mut d3d, &route.points, route.looped, Color::MAGENTA);
+            }
+            self.draw_route(&mut d3d, &self.route, false, Color::ORANGE);
+
+            // Draw cursor
+            if let Some(coord) = self.cursor {
+                d3d.draw_cube_wires(
+                    Vector3::new(
+                        coord.x as f32 * tile_size,
+                        coord.y as f32 * tile_size,
+                        coord.z as f32 * tile_size,
+                    ),
+                    tile_size,
+                    tile_size,
+                    tile_size,
+                    Color::RED,
+                );
+            }
+        });
+
+        // Draw crosshair
+        let center_x = d.get_screen_width() / 2;
+        let center_y = d.get_screen_height() / 2;
+        d.draw_line(center_x - 8, center_y, center_x + 8, center_y, Color::BLACK);
+        d.draw_line(center_x, center_y - 8, center_x, center_y + 8, Color::BLACK);
+
+        // Draw editor info and controls
+        let cursor = match self.cursor {
+            Some(coord) => format!("{}, {}", coord.x, coord.z),
+            None => "-".to_string(),
+        };
+        let unsaved = if self.dirty { " (unsaved)" } else { "" };
+        let lines = [
+            format!("Editing: {}{unsaved}", self.filename),
+            format!("Tool: {} (1-9)", self.tool.name()),
+            format!("Level: {} (wheel or R, F)", self.level),
+            format!("Cursor: {cursor}"),
+            "Left click: place, right click: remove".to_string(),
+            format!(
+                "Fly: {}, {}, C ({} for fast)",
+                self.input_map.key_names(&[
+                    Action::MoveForward,
+                    Action::StrafeLeft,
+                    Action::MoveBackward,
+                    Action::StrafeRight,
+                ]),
+                self.input_map.key_names(&[Action::Jump]),
+                self.input_map.key_names(&[Action::Sprint])
+            ),
+            "Route: Enter to finish, L to loop".to_string(),
+            "Ctrl+S: save, Escape: title screen".to_string(),
+        ];
+
+        d.draw_rectangle(10, 10, 260, 30 + lines.len() as i32 * 20, Color::GRAY);
+        d.draw_rectangle_lines(10, 10, 260, 30 + lines.len() as i32 * 20, Color::BLUE);
+        for (index, line) in lines.iter().enumerate() {
+            d.draw_text(line, 20, 20 + index as i32 * 20, 10, Color::BLACK);
+        }
+
+        if let Some(status) = &self.status {
+            d.draw_text(status, 10, d.get_screen_height() - 24, 14, Color::DARKBLUE);
+        }
+    }
+
+    // Move camera where it's looking, and up and down
+    fn fly(&mut self, actions: &ActionState, frame_time: f32, descend: bool) {
+        let (look_x, look_y) = actions.look();
+        self.mouse_look.apply_look(look_x, look_y);
+
+        let forward = build_camera_forward(self.mouse_look.yaw(), self.mouse_look.pitch());
+        let flat_forward = build_camera_forward(self.mouse_look.yaw(), 0.0);
+        let right = Vector3::new(-flat_forward.z, 0.0, flat_forward.x);
+        let forward = Vector3::new(forward.x, forward.y, forward.z);
+
+        let mut movement = forward * actions.forward_axis() + right * actions.strafe_axis();
+        if actions.is_down(Action::Jump) {
+            movement.y += 1.0;
+        }
+        if descend {
+            movement.y -= 1.0;
+        }
+
+        let speed = if actions.is_down(Action::Sprint) {
+            FAST_FLY_SPEED
+        } else {
+            FLY_SPEED
+        };
+
+        if movement.length() > 0.0 {
+            self.camera.position += movement.normalized() * speed * frame_time;
+        }
+        self.camera.target = self.camera.position + forward;
+    }
+
+    // Get the tile where the view ray crosses the middle of the current level
+    fn cursor_coord(&self) -> Option<GridCoord> {
+        let grid = &self.map.grid;
+        let origin = self.camera.position;
+        let direction = self.camera.target - self.camera.position;
+
+        // Looking level with or away from the level
+        let plane_y = self.level as f32 * grid.tile_size;
+        let distance = (plane_y - origin.y) / direction.y;
+        if !distance.is_finite() || distance <= 0.0 || distance > CURSOR_REACH {
+            return None;
+        }
+
+        let point = origin + direction * distance;
+        let x = (point.x / grid.tile_size).round();
+        let z = (point.z / grid.tile_size).round();
+
+        if x < 0.0 || z < 0.0 || x >= grid.width as f32 || z >= grid.height as f32 {
+            return None;
+        }
+
+        Some(GridCoord {
+            x: x as usize,
+            y: self.level,
+            z: z as usize,
+        })
+    }
+
+    // Use the current tool at a tile
+    fn place(&mut self, coord: GridCoord) {
+        self.dirty = true;
+
+        let spawns = &mut self.map.spawn_points;
+        let column = GridCoord { y: 0, ..coord };
+
+        match self.tool {
+            EditorTool::Grass | EditorTool::Stone | EditorTool::Exit => {
+                if let Some(kind) = self.tool.tile_type() {
+                    self.set_tile(coord, kind);
+                }
+            }
+            EditorTool::PlayerStart => spawns.player = Some(column),
+            EditorTool::Witch => add_spawn(&mut spawns.witches, column),
+            EditorTool::Tree => {
+                if !spawns.trees.iter().any(|tree| tree.coord == column) {
+                    spawns.trees.push(TreeSpawn {
+                        coord: column,
+                        leaf_width: None,
+                        leaf_height: None,
+                        trunk_height: None,
+                        leaf_color: None,
+                        trunk_color: None,
+                    });
+                }
+            }
+            EditorTool::Ball => add_spawn(&mut spawns.balls, column),
+            EditorTool::Item => add_spawn(&mut spawns.items, column),
+            EditorTool::PatrolRoute => self.route.push(column),
+        }
+    }
+
+    // Undo the current tool at a tile
+    fn remove(&mut self, coord: GridCoord) {
+        self.dirty = true;
+
+        let spawns = &mut self.map.spawn_points;
+        let column = GridCoord { y: 0, ..coord };
+
+        match self.tool {
+            EditorTool::Grass | EditorTool::Stone | EditorTool::Exit => {
+                if self.map.grid.remove(coord).is_some()
+                    && let Some(terrain) = &mut self.terrain
+                {
+                    terrain.mark_changed(&[coord]);
+                }
+            }
+            EditorTool::PlayerStart => {
+                if spawns.player == Some(column) {
+                    spawns.player = None;
+                }
+            }
+            EditorTool::Witch => spawns.witches.retain(|spawn| *spawn != column),
+            EditorTool::Tree => spawns.trees.retain(|tree| tree.coord != column),
+            EditorTool::Ball => spawns.balls.retain(|spawn| *spawn != column),
+            EditorTool::Item => spawns.items.retain(|spawn| *spawn != column),
+            // Take back the last point, or delete routes through this tile if none are being drawn
+            EditorTool::PatrolRoute => {
+                if self.route.pop().is_none() {
+                    self.map
+                        .patrol_routes
+                        .retain(|route| !route.points.contains(&column));
+                }
+            }
+        }
+    }
+
+    // Set a tile and rebake the terrain around it
+    fn set_tile(&mut self, coord: GridCoord, kind: TileType) {
+        self.map.grid.set(Tile { kind, coord });
+
+        if let Some(terrain) = &mut self.terrain {
+            terrain.mark_changed(&[coord]);
+        }
+    }
+
+    // Add the patrol route being drawn to the map
+    fn finish_route(&mut self, looped: bool) {
+        if self.route.is_empty() {
+            return;
+        }
+
+        self.map.patrol_routes.push(PatrolRoute {
+            points: std::mem::take(&mut self.route),
+            looped,
+        });
+    }
+
+    // Save map to its file
+    fn save(&mut self) {
+        self.finish_route(false);
+
+        let status = match write_map(&self.filename, &self.map) {
+            Ok(()) => {
+                self.dirty = false;
+                format!("Saved to {}", self.filename)
+            }
+            Err(e) => format!("Failed to save {}: {e}", self.filename),
+        };
+
+        println!("{status}");
+        self.status = Some(status);
+    }
+
+    // Position of a spawn or route marker, resting on the ground
+    fn marker_position(&self, coord: GridCoord) -> Vector3 {
+        let grid = &self.map.grid;
+        let ground = grid.surface_height(coord.x, coord.z) as f32 * grid.tile_size;
+
+        Vector3::new(
+            coord.x as f32 * grid.tile_size,
+            ground - grid.tile_size / 2.0 + 0.5,
+            coord.z as f32 * grid.tile_size,
+        )
+    }
+
+    // Draw a patrol route through its points
+    fn draw_route(
+        &self,
+        d3d: &mut RaylibMode3D<RaylibDrawHandle>,
+        points: &[GridCoord],
+        looped: bool,
+        color: Color,
+    ) {
+        for pair in points.windows(2) {
+            d3d.draw_line_3D(
+                self.marker_position(pair[0]),
+                self.marker_position(pair[1]),
+                color,
+            );
+        }
+
+        if looped && let (Some(first), Some(last)) = (points.first(), points.last()) {
+            d3d.draw_line_3D(
+                self.marker_position(*last),
+                self.marker_position(*first),
+                color,
+            );
+        }
+
+        for point in points {
+            d3d.draw_sphere(self.marker_position(*point), 0.15, color);
+        }
+    }
+}
+
+// Add a spawn point unless one's already there
+fn add_spawn(spawns: &mut Vec<GridCoord>, coord: GridCoord) {
+    if !spawns.contains(&coord) {
+        spawns.push(coord);
+    }
+}
@@ -1,30 +1,105 @@
 use crate::components::*;
-use crate::physics::*;
+use crate::input::actions::*;
+use crate::input::bindings::*;
+use crate::input::replay::*;
+use crate::pause::*;
+use crate::results::*;
+use crate::save::*;
 use crate::settings::*;
-use crate::systems::ai::*;
+use crate::simulation::*;
+use crate::systems::camera::*;
 use crate::systems::drawing::*;
-use crate::systems::player::*;
-use crate::systems::spawn::*;
-use crate::systems::terrain::*;
-use crate::world::grid::*;
-use crate::world::loader::*;
-use crate::State;
-use hecs::World;
+use crate::systems::terrain_mesh::*;
+use crate::world::loader::MapError;
+use crate::world::maps::*;
+use crate::{State, Transition};
 use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Longest frame time fed into the physics accumulator
+const MAX_FRAME_TIME: f32 = 0.25;
+
+// Game mode
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum GameMode {
+    SingleMap(MapInfo),
+    Campaign { maps: Vec<MapInfo>, current: usize },
+}
+
+// Functions for GameMode
+impl GameMode {
+    // Map being played
+    pub fn current_map(&self) -> &MapInfo {
+        match self {
+            GameMode::SingleMap(map) => map,
+            GameMode::Campaign { maps, current } => &maps[*current],
+        }
+    }
+
+    // Mode for the first map, to play the whole run again
+    pub fn first(&self) -> GameMode {
+        match self {
+            GameMode::SingleMap(map) => GameMode::SingleMap(map.clone()),
+            GameMode::Campaign { maps, .. } => GameMode::Campaign {
+                maps: maps.clone(),
+                current: 0,
+            },
+        }
+    }
+
+    // Mode for the next map in the campaign, if there is one
+    pub fn next(&self) -> Option<GameMode> {
+        match self {
+            GameMode::SingleMap(_) => None,
+            GameMode::Campaign { maps, current } => {
+                (current + 1 < maps.len()).then(|| GameMode::Campaign {
+                    maps: maps.clone(),
+                    current: current + 1,
+                })
+            }
+        }
+    }
+
+    // Label for the HUD, e.g. "forest (2/3)"
+    pub fn label(&self) -> String {
+        match self {
+            GameMode::SingleMap(map) => map.name.clone(),
+            GameMode::Campaign { maps, current } => {
+                format!("{} ({}/{})", maps[*current].name, current + 1, maps.len())
+            }
+        }
+    }
+}
 
 // Game
+// Windowed front end: turns Raylib input into commands for the simulation and renders it
 pub struct Game {
-    pub ecs_world: World,
-    pub physics_world: PhysicsWorld,
-    pub grid: Grid,
+    pub simulation: Simulation,
     pub camera: Camera3D,
-    pub mouse_look: MouseLook,
+    pub input_map: InputMap,
+    pub settings: Settings,
+    pub mode: GameMode,
+    pub start_stats: PlayerStats,
+    pub recording: Option<Replay>,
+    pub playback: Option<ReplayPlayback>,
+    pub accumulator: f32,
+    pub pending_actions: ActionState,
+    pub terrain: Option<TerrainModels>,
 }
 
 // Functions for Game
 impl Game {
-    // Start a new game
-    pub fn new() -> Result<Self, tiled::Error> {
+    // Start a new game on the mode's current map, carrying over player stats
+    pub fn new(
+        settings: &Settings,
+        mode: GameMode,
+        stats: PlayerStats,
+        seed: u64,
+    ) -> Result<Self, MapError> {
+        // Create simulation
+        let simulation = Simulation::new(settings, &mode.current_map().filename, stats, seed)?;
+
         // Create camera
         let mut camera = Camera3D::perspective(
             Vector3::new(0.0, 2.0, 4.0),
@@ -33,135 +108,408 @@ impl Game {
             60.0,
         );
 
-        // Create mouse look
-        let mouse_look = MouseLook::new(MOUSE_SENSITIVITY);
+        // Set camera position to player start position
+        if let Some((_, (_, body_handle))) = simulation
+            .ecs_world
+            .query::<(&Player, &BodyHandle)>()
+            .iter()
+            .next()
+        {
+            update_camera(
+                &mut camera,
+                &simulation.physics_world,
+                body_handle,
+                simulation.mouse_look.yaw(),
+                simulation.mouse_look.pitch(),
+                1.0,
+            );
+        }
+
+        // Load input bindings
+        let input_map = InputMap::load_or_default(&settings.input_config);
 
-        // Create ECS world
-        let mut ecs_world = World::new();
+        // Record a replay of this map
+        let recording = Some(Replay::new(
+            settings,
+            mode.current_map(),
+            seed,
+            &simulation.behaviour,
+        ));
 
-        // Create physics world
-        let mut physics_world = PhysicsWorld::new();
+        Ok(Self {
+            simulation,
+            camera,
+            input_map,
+            settings: settings.clone(),
+            mode,
+            start_stats: stats,
+            recording,
+            playback: None,
+            accumulator: 0.0,
+            pending_actions: ActionState::default(),
+            terrain: None,
+        })
+    }
 
-        // Load tile map and get grid
-        let mut grid = load_tiled_map("map_01.tmx")?;
+    // Start playing back a replay
+    pub fn from_replay(settings: &Settings, replay: Replay) -> Result<Self, MapError> {
+        // Use the settings the replay was recorded with
+        let settings = replay.settings(settings);
 
-        // Generate blocks
-        generate_blocks(&mut ecs_world, &mut physics_world, &grid);
+        let mut game = Game::new(
+            &settings,
+            GameMode::SingleMap(replay.map.clone()),
+            PlayerStats::default(),
+            replay.seed(),
+        )?;
 
-        // Generate player
-        let player_start_position = generate_player(&mut ecs_world, &mut physics_world, &grid);
+        game.recording = None;
+        game.playback = Some(ReplayPlayback::new(replay));
 
-        // Set camera position to player start position
-        camera.position = player_start_position.to_raylib_vec3(grid.tile_size);
+        Ok(game)
+    }
 
-        // Generate trees
-        generate_trees(&mut ecs_world, &mut physics_world, &grid, NUM_OF_TREES);
+    // Change mouse sensitivity mid-run
+    // A replay can't reproduce the change, so recording stops for this map
+    pub fn set_mouse_sensitivity(&mut self, mouse_sensitivity: f32) {
+        self.settings.mouse_sensitivity = mouse_sensitivity;
+        self.simulation.mouse_look.mouse_sensitivity = mouse_sensitivity;
 
-        // Generate balls
-        generate_balls(&mut ecs_world, &mut physics_world, &grid, NUM_OF_BALLS);
+        if self.recording.take().is_some() {
+            println!("Stopped recording replay: mouse sensitivity changed.");
+        }
+    }
 
-        // Generate witches
-        generate_witches(&mut ecs_world, &mut physics_world, &grid, NUM_OF_WITCHES);
+    // Turn debug mode on or off
+    pub fn set_debug_mode(&mut self, debug_mode: bool) {
+        self.settings.debug_mode = debug_mode;
+        self.settings.activate();
+    }
 
-        // Add trees to grid
-        for (_, (tree, body_handle)) in ecs_world.query::<(&Tree, &BodyHandle)>().iter() {
-            if let Some(body) = physics_world.bodies.get(body_handle.body_handle) {
-                let pos = body.translation();
-                let converted_pos = GridCoord::from_rapier3d_vec(*pos);
-                let radius = (tree.leaf_width / 2.0).floor() as isize;
+    // Start the current map again from the beginning
+    pub fn restart(&self) -> Result<Self, MapError> {
+        match &self.playback {
+            Some(playback) => Game::from_replay(&self.settings, playback.replay.clone()),
+            None => Game::new(
+                &self.settings,
+                self.mode.clone(),
+                self.start_stats,
+                self.simulation.seed,
+            ),
+        }
+    }
 
-                grid.fill_area(converted_pos, radius, TileType::Tree);
-            }
+    // Play the whole run again from the first map with the same seed
+    pub fn retry(&self) -> Result<Self, MapError> {
+        match &self.playback {
+            Some(playback) => Game::from_replay(&self.settings, playback.replay.clone()),
+            None => Game::new(
+                &self.settings,
+                self.mode.first(),
+                PlayerStats::default(),
+                self.simulation.seed,
+            ),
         }
+    }
+
+    // Load a saved game
+    pub fn load(settings: &Settings, path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        let save = SaveGame::read(path)?;
+
+        // Restore simulation
+        let simulation = Simulation::from_state(settings, save.simulation);
+
+        // Restore camera
+        let camera = Camera3D::perspective(
+            vector3_from_array(save.camera.position),
+            vector3_from_array(save.camera.target),
+            vector3_from_array(save.camera.up),
+            save.camera.fovy,
+        );
+
+        // Load input bindings
+        let input_map = InputMap::load_or_default(&settings.input_config);
 
         Ok(Self {
-            ecs_world,
-            physics_world,
-            grid,
+            simulation,
             camera,
-            mouse_look,
+            input_map,
+            settings: settings.clone(),
+            mode: save.mode,
+            start_stats: save.start_stats,
+            recording: None,
+            playback: None,
+            accumulator: 0.0,
+            pending_actions: ActionState::default(),
+            terrain: None,
         })
     }
 
+    // Save game to file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let save = SaveGame {
+            version: SAVE_VERSION,
+            mode: self.mode.clone(),
+            start_stats: self.start_stats,
+            camera: CameraState {
+                position: self.camera.position.to_array(),
+                target: self.camera.target.to_array(),
+                up: self.camera.up.to_array(),
+                fovy: self.camera.fovy,
+            },
+            simulation: self.simulation.save_state(),
+        };
+
+        save.write(path)
+    }
+
     // Update
-    pub fn update(&mut self, rl: &mut RaylibHandle, next_state: &mut Option<State>) {
+    pub fn update(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        transition: &mut Option<Transition>,
+    ) {
+        // Bake terrain meshes on the first update, once there's a thread to upload them from
+        if self.terrain.is_none() {
+            self.terrain = Some(TerrainModels::build(rl, thread, &self.simulation.grid));
+        }
+
         // If cursor is showing then disable it
         if !rl.is_cursor_hidden() {
             rl.disable_cursor();
         }
-        // Get mouse info
-        self.mouse_look.update_from_mouse(rl);
 
-        // Get player from ECS
+        // Read actions from keyboard, mouse, and gamepad
+        let actions = self.input_map.read(rl);
+
+        // Pause, freezing the game until the pause menu is closed
+        if actions.is_pressed(Action::Pause) {
+            *transition = Some(Transition::Push(State::Pause(PauseMenu::new())));
+            return;
+        }
+
+        // Quick save
+        if self.playback.is_none() && actions.is_pressed(Action::QuickSave) {
+            match self.save(QUICKSAVE_PATH) {
+                Ok(()) => println!("Game saved to {QUICKSAVE_PATH}"),
+                Err(e) => eprintln!("Failed to save game: {e}"),
+            }
+        }
+
+        // Quick load
+        if self.playback.is_none() && actions.is_pressed(Action::QuickLoad) {
+            match Game::load(&self.settings, QUICKSAVE_PATH) {
+                Ok(game) => {
+                    println!("Game loaded from {QUICKSAVE_PATH}");
+                    *transition = Some(Transition::Switch(State::Game(Box::new(game))));
+                    return;
+                }
+                Err(e) => eprintln!("Failed to load game: {e}"),
+            }
+        }
+
+        // Keep this frame's input until a step uses it
+        self.pending_actions.merge(&actions);
+
+        // Add frame time, capped so a long stall doesn't cause a burst of steps
+        self.accumulator += rl.get_frame_time().min(MAX_FRAME_TIME);
+
+        // Step simulation at a fixed rate, independent of frame rate
+        let timestep = self.simulation.physics_world.timestep();
+
+        while self.accumulator >= timestep {
+            self.accumulator -= timestep;
+
+            // Use actions from the replay being played, or the pending input
+            let step_actions = match &mut self.playback {
+                Some(playback) => match playback.next_actions() {
+                    Some(actions) => actions,
+                    None => {
+                        println!("Replay finished.");
+
+                        // Set next state to Title Screen
+                        *transition = Some(Transition::Switch(State::TitleScreen));
+                        return;
+                    }
+                },
+                None => self.pending_actions,
+            };
+
+            // Presses and look only count for the first step that uses them
+            self.pending_actions = self.pending_actions.held();
+
+            // Record actions
+            if let Some(recording) = &mut self.recording {
+                recording.record(&step_actions);
+            }
+
+            // Step simulation
+            let outcome = self.simulation.step(&step_actions);
+
+            // Remember which terrain chunks need rebaking after tiles were dug or placed
+            if let Some(terrain) = &mut self.terrain {
+                terrain.mark_changed(&self.simulation.changed_tiles);
+            }
+
+            if outcome != StepOutcome::Running {
+                self.handle_outcome(outcome, transition);
+                return;
+            }
+        }
+
+        // Rebake terrain chunks that changed this frame
+        if let Some(terrain) = &mut self.terrain {
+            terrain.rebuild_changed(rl, thread, &self.simulation.grid);
+        }
+
+        // Update camera
+        let alpha = self.interpolation_alpha();
+
         if let Some((_, (_, body_handle))) = self
+            .simulation
             .ecs_world
-            .query::<(&mut Player, &BodyHandle)>()
+            .query::<(&Player, &BodyHandle)>()
             .iter()
             .next()
         {
-            // Handle player movement
-            handle_player_movement(
-                &mut self.physics_world,
-                rl,
-                body_handle,
-                self.mouse_look.yaw(),
-            );
-
-            // Update physics world
-            self.physics_world.step();
-
-            // Update camera
             update_camera(
                 &mut self.camera,
-                &self.physics_world,
+                &self.simulation.physics_world,
                 body_handle,
-                self.mouse_look.yaw(),
-                self.mouse_look.pitch(),
+                self.simulation.mouse_look.yaw(),
+                self.simulation.mouse_look.pitch(),
+                alpha,
             );
-        } else {
-            // Update physics world
-            self.physics_world.step();
         }
+    }
+
+    // How far the frame is between the last step and the next (0.0 to 1.0)
+    fn interpolation_alpha(&self) -> f32 {
+        (self.accumulator / self.simulation.physics_world.timestep()).clamp(0.0, 1.0)
+    }
+
+    // Handle the end of a map
+    fn handle_outcome(&mut self, outcome: StepOutcome, transition: &mut Option<Transition>) {
+        match outcome {
+            StepOutcome::Running => {}
+            // If the player lost show the results over the game
+            StepOutcome::Lost(cause) => {
+                println!("GAME OVER! {}", cause.description());
+                self.write_replay();
+
+                *transition = Some(self.show_results(RunOutcome::Lost(cause)));
+            }
+            // If map was cleared move on to the next one, keeping player stats
+            StepOutcome::Won => {
+                self.write_replay();
+
+                match self.mode.next() {
+                    Some(mode) => {
+                        println!("MAP CLEARED! Next map: {}", mode.label());
+
+                        match Game::new(
+                            &self.settings,
+                            mode,
+                            self.simulation.stats,
+                            self.simulation.seed,
+                        ) {
+                            Ok(game) => {
+                                *transition = Some(Transition::Switch(State::Game(Box::new(game))))
+                            }
+                            Err(e) => {
+                                eprintln!("Failed to create game: {e}");
+                                *transition = Some(Transition::Switch(State::TitleScreen));
+                            }
+                        }
+                    }
+                    None => {
+                        println!(
+                            "YOU WIN! Maps cleared: {}, time survived: {:.1}s",
+                            self.simulation.stats.maps_completed,
+                            self.simulation.stats.time_survived
+                        );
+
+                        *transition = Some(self.show_results(RunOutcome::Won));
+                    }
+                }
+            }
+        }
+    }
 
-        // Update witch AI
-        if update_witch_ai(&mut self.ecs_world, &mut self.physics_world) {
-            // If witch AI returns game over
-            println!("GAME OVER!");
+    // Transition to the results screen for this run
+    // Replays are shown but kept out of the high score table
+    fn show_results(&self, outcome: RunOutcome) -> Transition {
+        let summary = RunSummary {
+            outcome,
+            label: self.mode.label(),
+            seed: self.simulation.seed,
+            stats: self.simulation.stats,
+        };
+
+        let results = ResultsScreen::new(summary, self.playback.is_none());
+
+        Transition::Push(State::Results(results))
+    }
 
-            // Set next state to Title Screen
-            *next_state = Some(State::TitleScreen);
+    // Write the recorded replay of this map, if recording
+    fn write_replay(&self) {
+        if let Some(recording) = &self.recording {
+            match recording.write(LAST_REPLAY_PATH) {
+                Ok(()) => println!("Replay saved to {LAST_REPLAY_PATH}"),
+                Err(e) => eprintln!("Failed to save replay: {e}"),
+            }
         }
     }
 
     // Render
-    pub fn render(&self, rl: &mut RaylibHandle, thread: &RaylibThread) {
-        // Begin drawing frame
-        let mut d = rl.begin_drawing(thread);
+    pub fn render(&self, d: &mut RaylibDrawHandle) {
+        let ecs_world = &self.simulation.ecs_world;
+        let physics_world = &self.simulation.physics_world;
+        let alpha = self.interpolation_alpha();
 
         // Clear frame
         d.clear_background(Color::SKYBLUE);
 
         // Draw 3D objects
         d.draw_mode3D(self.camera, |mut d3d, _camera| {
-            // Draw blocks
-            draw_blocks(&mut d3d, &self.ecs_world, &self.physics_world);
+            // Draw terrain
+            if let Some(terrain) = &self.terrain {
+                terrain.draw(&mut d3d);
+            }
 
             // Draw forest
-            draw_forest(&mut d3d, &self.ecs_world, &self.physics_world);
+            draw_forest(&mut d3d, ecs_world, physics_world);
 
             // Draw balls
-            draw_balls(&mut d3d, &self.ecs_world, &self.physics_world);
+            draw_balls(&mut d3d, ecs_world, physics_world, alpha);
+
+            // Draw items
+            draw_items(&mut d3d, ecs_world, physics_world);
 
             // Draw witches
-            draw_witches(&mut d3d, &self.ecs_world, &self.physics_world);
+            draw_witches(&mut d3d, ecs_world, physics_world, alpha);
 
             // Draw collision wireframes
-            if DEBUG_MODE {
-                debug_colliders(&mut d3d, &self.physics_world, Color::RED);
+            if self.settings.debug_mode {
+                debug_colliders(&mut d3d, physics_world, Color::RED);
             }
         });
 
         // Draw HUD
-        draw_hud(&mut d);
+        draw_hud(d, &self.input_map);
+        draw_run_info(d, &self.mode.label(), self.simulation.seed);
+        draw_objectives(
+            d,
+            &self
+                .simulation
+                .objectives
+                .describe(self.simulation.map_time),
+        );
     }
 }
+
+// Raylib Vector3 from an [x, y, z] array
+fn vector3_from_array([x, y, z]: [f32; 3]) -> Vector3 {
+    Vector3::new(x, y, z)
+}
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+// Actions the player can perform
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    StrafeLeft,
+    StrafeRight,
+    Look,
+    Jump,
+    Sprint,
+    Interact,
+    Dig,
+    PlaceGrass,
+    PlaceStone,
+    Pause,
+    QuickSave,
+    QuickLoad,
+}
+
+// Functions for Action
+impl Action {
+    // All actions
+    pub const ALL: [Action; 14] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::StrafeLeft,
+        Action::StrafeRight,
+        Action::Look,
+        Action::Jump,
+        Action::Sprint,
+        Action::Interact,
+        Action::Dig,
+        Action::PlaceGrass,
+        Action::PlaceStone,
+        Action::Pause,
+        Action::QuickSave,
+        Action::QuickLoad,
+    ];
+
+    // Get action from its name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Action::ALL
+            .into_iter()
+            .find(|action| action.name().eq_ignore_ascii_case(name))
+    }
+
+    // Get name of action
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveForward => "MoveForward",
+            Action::MoveBackward => "MoveBackward",
+            Action::StrafeLeft => "StrafeLeft",
+            Action::StrafeRight => "StrafeRight",
+            Action::Look => "Look",
+            Action::Jump => "Jump",
+            Action::Sprint => "Sprint",
+            Action::Interact => "Interact",
+            Action::Dig => "Dig",
+            Action::PlaceGrass => "PlaceGrass",
+            Action::PlaceStone => "PlaceStone",
+            Action::Pause => "Pause",
+            Action::QuickSave => "QuickSave",
+            Action::QuickLoad => "QuickLoad",
+        }
+    }
+
+    // Index into per-action arrays
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+// State of every action for a single frame
+// Built from devices by an InputMap, or by hand for tests and replays
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActionState {
+    values: [f32; Action::ALL.len()],
+    pressed: [bool; Action::ALL.len()],
+    look: (f32, f32),
+}
+
+// Functions for ActionState
+impl ActionState {
+    // How strongly an action is held (0.0 to 1.0)
+    pub fn value(&self, action: Action) -> f32 {
+        self.values[action.index()]
+    }
+
+    // Is action held
+    pub fn is_down(&self, action: Action) -> bool {
+        self.value(action) > 0.0
+    }
+
+    // Was action pressed this frame
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.pressed[action.index()]
+    }
+
+    // Look delta for this frame
+    pub fn look(&self) -> (f32, f32) {
+        self.look
+    }
+
+    // Hold an action, keeping the strongest value if bound more than once
+    pub fn hold(&mut self, action: Action, value: f32) {
+        let current = &mut self.values[action.index()];
+        *current = current.max(value.clamp(0.0, 1.0));
+    }
+
+    // Press an action this frame
+    pub fn press(&mut self, action: Action) {
+        self.pressed[action.index()] = true;
+        self.hold(action, 1.0);
+    }
+
+    // Add to look delta
+    pub fn add_look(&mut self, x: f32, y: f32) {
+        self.look.0 += x;
+        self.look.1 += y;
+    }
+
+    // Add a later frame's input on top of input no step has used yet
+    // Held values are replaced, presses and look are kept until used
+    pub fn merge(&mut self, later: &ActionState) {
+        self.values = later.values;
+
+        for (pressed, later_pressed) in self.pressed.iter_mut().zip(later.pressed) {
+            *pressed |= later_pressed;
+        }
+
+        self.add_look(later.look.0, later.look.1);
+    }
+
+    // Same held actions with presses and look used up
+    pub fn held(&self) -> ActionState {
+        ActionState {
+            values: self.values,
+            ..ActionState::default()
+        }
+    }
+
+    // Forward/backward movement axis (-1.0 to 1.0)
+    pub fn forward_axis(&self) -> f32 {
+        self.value(Action::MoveForward) - self.value(Action::MoveBackward)
+    }
+
+    // Left/right movement axis (-1.0 to 1.0)
+    pub fn strafe_axis(&self) -> f32 {
+        self.value(Action::StrafeRight) - self.value(Action::StrafeLeft)
+    }
+}
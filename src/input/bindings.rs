@@ -0,0 +1,472 @@
+use crate::input::actions::*;
+use raylib::consts::GamepadAxis::*;
+use raylib::consts::GamepadButton::*;
+use raylib::consts::KeyboardKey::*;
+use raylib::consts::MouseButton::*;
+use raylib::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+// Device input an action can be bound to
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Binding {
+    Key(KeyboardKey),
+    MouseButton(MouseButton),
+    MouseMotion,
+    GamepadButton(GamepadButton),
+    GamepadAxis(GamepadAxis, f32),
+    GamepadStick(GamepadAxis, GamepadAxis),
+}
+
+// Functions for Binding
+impl Binding {
+    // Parse binding from config text, e.g. "key:W", "mouse:Left", "axis:LeftY-"
+    pub fn parse(text: &str) -> Option<Self> {
+        let (device, name) = text.split_once(':')?;
+
+        match device.trim().to_ascii_lowercase().as_str() {
+            "key" => parse_key(name.trim()).map(Binding::Key),
+            "mouse" => match name.trim().to_ascii_lowercase().as_str() {
+                "motion" => Some(Binding::MouseMotion),
+                button => parse_mouse_button(button).map(Binding::MouseButton),
+            },
+            "gamepad" => parse_gamepad_button(name.trim()).map(Binding::GamepadButton),
+            "axis" => {
+                let name = name.trim();
+                let (axis, sign) = match name.strip_suffix('-') {
+                    Some(axis) => (axis, -1.0),
+                    None => (name.strip_suffix('+').unwrap_or(name), 1.0),
+                };
+
+                parse_gamepad_axis(axis).map(|axis| Binding::GamepadAxis(axis, sign))
+            }
+            "stick" => match name.trim().to_ascii_lowercase().as_str() {
+                "left" => Some(Binding::GamepadStick(
+                    GAMEPAD_AXIS_LEFT_X,
+                    GAMEPAD_AXIS_LEFT_Y,
+                )),
+                "right" => Some(Binding::GamepadStick(
+                    GAMEPAD_AXIS_RIGHT_X,
+                    GAMEPAD_AXIS_RIGHT_Y,
+                )),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+// Input map
+// Binds actions to keyboard, mouse, and gamepad inputs
+#[derive(Clone, Debug)]
+pub struct InputMap {
+    pub bindings: HashMap<Action, Vec<Binding>>,
+    pub gamepad: i32,
+    pub deadzone: f32,
+    pub gamepad_look_speed: f32,
+}
+
+// Input config file layout
+#[derive(Deserialize)]
+struct InputConfig {
+    gamepad: Option<i32>,
+    deadzone: Option<f32>,
+    gamepad_look_speed: Option<f32>,
+    #[serde(default)]
+    bindings: HashMap<String, Vec<String>>,
+}
+
+// Functions for InputMap
+impl InputMap {
+    // Load input map from config file
+    // Actions missing from the file keep their default bindings
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, InputConfigError> {
+        let text = std::fs::read_to_string(path).map_err(InputConfigError::Io)?;
+        let config: InputConfig = toml::from_str(&text).map_err(InputConfigError::Parse)?;
+
+        let mut input_map = Self::default();
+
+        if let Some(gamepad) = config.gamepad {
+            input_map.gamepad = gamepad;
+        }
+        if let Some(deadzone) = config.deadzone {
+            input_map.deadzone = deadzone;
+        }
+        if let Some(gamepad_look_speed) = config.gamepad_look_speed {
+            input_map.gamepad_look_speed = gamepad_look_speed;
+        }
+
+        for (name, texts) in config.bindings {
+            let action = Action::from_name(&name)
+                .ok_or_else(|| InputConfigError::UnknownAction(name.clone()))?;
+
+            let bindings = texts
+                .iter()
+                .map(|text| {
+                    Binding::parse(text).ok_or_else(|| InputConfigError::InvalidBinding {
+                        action: name.clone(),
+                        binding: text.clone(),
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            input_map.bindings.insert(action, bindings);
+        }
+
+        Ok(input_map)
+    }
+
+    // Load input map from config file or fall back to defaults
+    pub fn load_or_default(path: impl AsRef<Path>) -> Self {
+        match Self::load(&path) {
+            Ok(input_map) => input_map,
+            Err(e) => {
+                eprintln!(
+                    "Failed to load input config {}: {e}. Using default bindings.",
+                    path.as_ref().display()
+                );
+                Self::default()
+            }
+        }
+    }
+
+    // Get names of the keys bound to actions, for showing controls on screen
+    pub fn key_names(&self, actions: &[Action]) -> String {
+        let names: Vec<&str> = actions
+            .iter()
+            .flat_map(|action| self.bindings.get(action).into_iter().flatten())
+            .filter_map(|binding| match binding {
+                Binding::Key(key) => key_name(*key),
+                _ => None,
+            })
+            .collect();
+
+        if names.is_empty() {
+            "unbound".to_string()
+        } else {
+            names.join(", ")
+        }
+    }
+
+    // Read devices into action state for this frame
+    pub fn read(&self, rl: &RaylibHandle) -> ActionState {
+        let mut actions = ActionState::default();
+
+        let gamepad_available = rl.is_gamepad_available(self.gamepad);
+
+        for (action, bindings) in &self.bindings {
+            for binding in bindings {
+                match *binding {
+                    Binding::Key(key) => {
+                        if rl.is_key_pressed(key) {
+                            actions.press(*action);
+                        } else if rl.is_key_down(key) {
+                            actions.hold(*action, 1.0);
+                        }
+                    }
+                    Binding::MouseButton(button) => {
+                        if rl.is_mouse_button_pressed(button) {
+                            actions.press(*action);
+                        } else if rl.is_mouse_button_down(button) {
+                            actions.hold(*action, 1.0);
+                        }
+                    }
+                    Binding::MouseMotion if *action == Action::Look => {
+                        let mouse_delta = rl.get_mouse_delta();
+                        actions.add_look(mouse_delta.x, mouse_delta.y);
+                    }
+                    Binding::GamepadButton(button) if gamepad_available => {
+                        if rl.is_gamepad_button_pressed(self.gamepad, button) {
+                            actions.press(*action);
+                        } else if rl.is_gamepad_button_down(self.gamepad, button) {
+                            actions.hold(*action, 1.0);
+                        }
+                    }
+                    Binding::GamepadAxis(axis, sign) if gamepad_available => {
+                        let value = rl.get_gamepad_axis_movement(self.gamepad, axis) * sign;
+
+                        if value > self.deadzone {
+                            actions.hold(*action, value);
+                        }
+                    }
+                    Binding::GamepadStick(axis_x, axis_y)
+                        if gamepad_available && *action == Action::Look =>
+                    {
+                        let x = rl.get_gamepad_axis_movement(self.gamepad, axis_x);
+                        let y = rl.get_gamepad_axis_movement(self.gamepad, axis_y);
+
+                        if x.abs() > self.deadzone || y.abs() > self.deadzone {
+                            actions
+                                .add_look(x * self.gamepad_look_speed, y * self.gamepad_look_speed);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        actions
+    }
+}
+
+// Default input map
+impl Default for InputMap {
+    fn default() -> Self {
+        let bindings = HashMap::from([
+            (
+                Action::MoveForward,
+                vec![
+                    Binding::Key(KEY_W),
+                    Binding::GamepadAxis(GAMEPAD_AXIS_LEFT_Y, -1.0),
+                ],
+            ),
+            (
+                Action::MoveBackward,
+                vec![
+                    Binding::Key(KEY_S),
+                    Binding::GamepadAxis(GAMEPAD_AXIS_LEFT_Y, 1.0),
+                ],
+            ),
+            (
+                Action::StrafeLeft,
+                vec![
+                    Binding::Key(KEY_A),
+                    Binding::GamepadAxis(GAMEPAD_AXIS_LEFT_X, -1.0),
+                ],
+            ),
+            (
+                Action::StrafeRight,
+                vec![
+                    Binding::Key(KEY_D),
+                    Binding::GamepadAxis(GAMEPAD_AXIS_LEFT_X, 1.0),
+                ],
+            ),
+            (
+                Action::Look,
+                vec![
+                    Binding::MouseMotion,
+                    Binding::GamepadStick(GAMEPAD_AXIS_RIGHT_X, GAMEPAD_AXIS_RIGHT_Y),
+                ],
+            ),
+            (
+                Action::Jump,
+                vec![
+                    Binding::Key(KEY_SPACE),
+                    Binding::GamepadButton(GAMEPAD_BUTTON_RIGHT_FACE_DOWN),
+                ],
+            ),
+            (
+                Action::Sprint,
+                vec![
+                    Binding::Key(KEY_LEFT_SHIFT),
+                    Binding::GamepadButton(GAMEPAD_BUTTON_LEFT_THUMB),
+                ],
+            ),
+            (
+                Action::Interact,
+                vec![
+                    Binding::Key(KEY_E),
+                    Binding::MouseButton(MOUSE_BUTTON_LEFT),
+                    Binding::GamepadButton(GAMEPAD_BUTTON_RIGHT_FACE_LEFT),
+                ],
+            ),
+            (
+                Action::Dig,
+                vec![
+                    Binding::Key(KEY_Q),
+                    Binding::MouseButton(MOUSE_BUTTON_RIGHT),
+                    Binding::GamepadButton(GAMEPAD_BUTTON_RIGHT_TRIGGER_1),
+                ],
+            ),
+            (
+                Action::PlaceGrass,
+                vec![
+                    Binding::Key(KEY_ONE),
+                    Binding::GamepadButton(GAMEPAD_BUTTON_LEFT_TRIGGER_1),
+                ],
+            ),
+            (
+                Action::PlaceStone,
+                vec![
+                    Binding::Key(KEY_TWO),
+                    Binding::GamepadButton(GAMEPAD_BUTTON_LEFT_TRIGGER_2),
+                ],
+            ),
+            (
+                Action::Pause,
+                vec![
+                    Binding::Key(KEY_P),
+                    Binding::Key(KEY_ESCAPE),
+                    Binding::GamepadButton(GAMEPAD_BUTTON_MIDDLE_RIGHT),
+                ],
+            ),
+            (Action::QuickSave, vec![Binding::Key(KEY_F5)]),
+            (Action::QuickLoad, vec![Binding::Key(KEY_F9)]),
+        ]);
+
+        Self {
+            bindings,
+            gamepad: 0,
+            deadzone: 0.2,
+            gamepad_look_speed: 12.0,
+        }
+    }
+}
+
+// Input config error
+#[derive(Debug)]
+pub enum InputConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    UnknownAction(String),
+    InvalidBinding { action: String, binding: String },
+}
+
+// Display input config error
+impl fmt::Display for InputConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputConfigError::Io(e) => write!(f, "{e}"),
+            InputConfigError::Parse(e) => write!(f, "{e}"),
+            InputConfigError::UnknownAction(name) => write!(f, "unknown action '{name}'"),
+            InputConfigError::InvalidBinding { action, binding } => {
+                write!(f, "invalid binding '{binding}' for action '{action}'")
+            }
+        }
+    }
+}
+
+// Keyboard key names, as written in config
+const KEY_NAMES: [(&str, KeyboardKey); 63] = [
+    ("A", KEY_A),
+    ("B", KEY_B),
+    ("C", KEY_C),
+    ("D", KEY_D),
+    ("E", KEY_E),
+    ("F", KEY_F),
+    ("G", KEY_G),
+    ("H", KEY_H),
+    ("I", KEY_I),
+    ("J", KEY_J),
+    ("K", KEY_K),
+    ("L", KEY_L),
+    ("M", KEY_M),
+    ("N", KEY_N),
+    ("O", KEY_O),
+    ("P", KEY_P),
+    ("Q", KEY_Q),
+    ("R", KEY_R),
+    ("S", KEY_S),
+    ("T", KEY_T),
+    ("U", KEY_U),
+    ("V", KEY_V),
+    ("W", KEY_W),
+    ("X", KEY_X),
+    ("Y", KEY_Y),
+    ("Z", KEY_Z),
+    ("0", KEY_ZERO),
+    ("1", KEY_ONE),
+    ("2", KEY_TWO),
+    ("3", KEY_THREE),
+    ("4", KEY_FOUR),
+    ("5", KEY_FIVE),
+    ("6", KEY_SIX),
+    ("7", KEY_SEVEN),
+    ("8", KEY_EIGHT),
+    ("9", KEY_NINE),
+    ("F1", KEY_F1),
+    ("F2", KEY_F2),
+    ("F3", KEY_F3),
+    ("F4", KEY_F4),
+    ("F5", KEY_F5),
+    ("F6", KEY_F6),
+    ("F7", KEY_F7),
+    ("F8", KEY_F8),
+    ("F9", KEY_F9),
+    ("F10", KEY_F10),
+    ("F11", KEY_F11),
+    ("F12", KEY_F12),
+    ("SPACE", KEY_SPACE),
+    ("ESCAPE", KEY_ESCAPE),
+    ("ENTER", KEY_ENTER),
+    ("TAB", KEY_TAB),
+    ("BACKSPACE", KEY_BACKSPACE),
+    ("UP", KEY_UP),
+    ("DOWN", KEY_DOWN),
+    ("LEFT", KEY_LEFT),
+    ("RIGHT", KEY_RIGHT),
+    ("LEFTSHIFT", KEY_LEFT_SHIFT),
+    ("RIGHTSHIFT", KEY_RIGHT_SHIFT),
+    ("LEFTCONTROL", KEY_LEFT_CONTROL),
+    ("RIGHTCONTROL", KEY_RIGHT_CONTROL),
+    ("LEFTALT", KEY_LEFT_ALT),
+    ("RIGHTALT", KEY_RIGHT_ALT),
+];
+
+// Parse keyboard key name
+fn parse_key(name: &str) -> Option<KeyboardKey> {
+    let name = name.to_ascii_uppercase();
+
+    KEY_NAMES
+        .iter()
+        .find(|(key_name, _)| *key_name == name)
+        .map(|(_, key)| *key)
+}
+
+// Get name of a keyboard key, as written in config
+fn key_name(key: KeyboardKey) -> Option<&'static str> {
+    KEY_NAMES
+        .iter()
+        .find(|(_, named_key)| *named_key == key)
+        .map(|(name, _)| *name)
+}
+
+// Parse mouse button name
+fn parse_mouse_button(name: &str) -> Option<MouseButton> {
+    match name.to_ascii_lowercase().as_str() {
+        "left" => Some(MOUSE_BUTTON_LEFT),
+        "right" => Some(MOUSE_BUTTON_RIGHT),
+        "middle" => Some(MOUSE_BUTTON_MIDDLE),
+        _ => None,
+    }
+}
+
+// Parse gamepad button name
+fn parse_gamepad_button(name: &str) -> Option<GamepadButton> {
+    let button = match name.to_ascii_lowercase().as_str() {
+        "facedown" => GAMEPAD_BUTTON_RIGHT_FACE_DOWN,
+        "faceright" => GAMEPAD_BUTTON_RIGHT_FACE_RIGHT,
+        "faceleft" => GAMEPAD_BUTTON_RIGHT_FACE_LEFT,
+        "faceup" => GAMEPAD_BUTTON_RIGHT_FACE_UP,
+        "dpadup" => GAMEPAD_BUTTON_LEFT_FACE_UP,
+        "dpaddown" => GAMEPAD_BUTTON_LEFT_FACE_DOWN,
+        "dpadleft" => GAMEPAD_BUTTON_LEFT_FACE_LEFT,
+        "dpadright" => GAMEPAD_BUTTON_LEFT_FACE_RIGHT,
+        "leftbumper" => GAMEPAD_BUTTON_LEFT_TRIGGER_1,
+        "rightbumper" => GAMEPAD_BUTTON_RIGHT_TRIGGER_1,
+        "lefttrigger" => GAMEPAD_BUTTON_LEFT_TRIGGER_2,
+        "righttrigger" => GAMEPAD_BUTTON_RIGHT_TRIGGER_2,
+        "select" => GAMEPAD_BUTTON_MIDDLE_LEFT,
+        "start" => GAMEPAD_BUTTON_MIDDLE_RIGHT,
+        "leftthumb" => GAMEPAD_BUTTON_LEFT_THUMB,
+        "rightthumb" => GAMEPAD_BUTTON_RIGHT_THUMB,
+        _ => return None,
+    };
+
+    Some(button)
+}
+
+// Parse gamepad axis name
+fn parse_gamepad_axis(name: &str) -> Option<GamepadAxis> {
+    match name.to_ascii_lowercase().as_str() {
+        "leftx" => Some(GAMEPAD_AXIS_LEFT_X),
+        "lefty" => Some(GAMEPAD_AXIS_LEFT_Y),
+        "rightx" => Some(GAMEPAD_AXIS_RIGHT_X),
+        "righty" => Some(GAMEPAD_AXIS_RIGHT_Y),
+        "lefttrigger" => Some(GAMEPAD_AXIS_LEFT_TRIGGER),
+        "righttrigger" => Some(GAMEPAD_AXIS_RIGHT_TRIGGER),
+        _ => None,
+    }
+}
@@ -0,0 +1,3 @@
+pub mod actions;
+pub mod bindings;
+pub mod replay;
@@ -0,0 +1,193 @@
+use crate::input::actions::*;
+use crate::settings::*;
+use crate::systems::behaviour::*;
+use crate::world::maps::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Replay file format version
+// Bump when the layout changes so old replays are rejected instead of misread
+pub const REPLAY_VERSION: u32 = 7;
+
+// Replay of the last run, written when a map ends
+pub const LAST_REPLAY_PATH: &str = "replays/last.toml";
+
+// Replay
+// Everything needed to play a map again exactly: the world seed, the settings that shape the
+// world, and the actions for every simulation step
+// Files too big to embed are stored as hashes, so playback can refuse them if they've changed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub map: MapInfo,
+    // Stored as an i64 bit pattern since TOML integers are signed
+    pub seed: i64,
+    pub mouse_sensitivity: f32,
+    pub num_of_trees: u32,
+    pub num_of_balls: u32,
+    pub num_of_witches: u32,
+    pub survive_seconds: f32,
+    pub physics_hz: f32,
+    // Hashes of the witch behaviour config in use and the map file, stored as i64 bit patterns
+    pub witch_config_hash: i64,
+    pub map_hash: i64,
+    pub frames: Vec<ActionState>,
+}
+
+// Functions for Replay
+impl Replay {
+    // Start recording a replay of a map
+    pub fn new(settings: &Settings, map: &MapInfo, seed: u64, behaviour: &WitchBehaviour) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            map: map.clone(),
+            seed: seed as i64,
+            mouse_sensitivity: settings.mouse_sensitivity,
+            num_of_trees: settings.num_of_trees,
+            num_of_balls: settings.num_of_balls,
+            num_of_witches: settings.num_of_witches,
+            survive_seconds: settings.survive_seconds,
+            physics_hz: settings.physics_hz,
+            witch_config_hash: text_hash(behaviour.config.as_bytes()) as i64,
+            map_hash: file_hash(PathBuf::from(MAPS_DIR).join(&map.filename)) as i64,
+            frames: Vec::new(),
+        }
+    }
+
+    // Record this step's actions
+    pub fn record(&mut self, actions: &ActionState) {
+        self.frames.push(*actions);
+    }
+
+    // World seed
+    pub fn seed(&self) -> u64 {
+        self.seed as u64
+    }
+
+    // Settings the replay was recorded with, on top of the current ones
+    pub fn settings(&self, settings: &Settings) -> Settings {
+        Settings {
+            mouse_sensitivity: self.mouse_sensitivity,
+            num_of_trees: self.num_of_trees,
+            num_of_balls: self.num_of_balls,
+            num_of_witches: self.num_of_witches,
+            survive_seconds: self.survive_seconds,
+            physics_hz: self.physics_hz,
+            seed: Some(self.seed()),
+            ..settings.clone()
+        }
+    }
+
+    // Check the witch behaviour config and map are the ones the replay was recorded with
+    // The behaviour is loaded as the simulation will load it, so built-in defaults are checked too
+    pub fn check_files(&self, settings: &Settings) -> Result<(), ReplayError> {
+        let behaviour = WitchBehaviour::load_or_default(&settings.witch_config);
+        if text_hash(behaviour.config.as_bytes()) as i64 != self.witch_config_hash {
+            return Err(ReplayError::Changed(settings.witch_config.clone()));
+        }
+
+        let map_path = PathBuf::from(MAPS_DIR).join(&self.map.filename);
+        if file_hash(&map_path) as i64 != self.map_hash {
+            return Err(ReplayError::Changed(map_path.display().to_string()));
+        }
+
+        Ok(())
+    }
+
+    // Write replay to file, creating its directory if needed
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let path = path.as_ref();
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(ReplayError::Io)?;
+        }
+
+        let text = toml::to_string(self).map_err(ReplayError::Serialize)?;
+
+        std::fs::write(path, text).map_err(ReplayError::Io)
+    }
+
+    // Read replay from file
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let text = std::fs::read_to_string(path).map_err(ReplayError::Io)?;
+
+        // Check version before reading the rest so old layouts give a clear error
+        #[derive(Deserialize)]
+        struct Header {
+            version: u32,
+        }
+
+        let header: Header = toml::from_str(&text).map_err(ReplayError::Parse)?;
+        if header.version != REPLAY_VERSION {
+            return Err(ReplayError::Version(header.version));
+        }
+
+        toml::from_str(&text).map_err(ReplayError::Parse)
+    }
+}
+
+// Replay playback
+// Feeds recorded actions back one step at a time
+#[derive(Clone, Debug)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    pub frame: usize,
+}
+
+// Functions for ReplayPlayback
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self { replay, frame: 0 }
+    }
+
+    // Actions for the next step, or None once the replay has run out
+    pub fn next_actions(&mut self) -> Option<ActionState> {
+        let actions = self.replay.frames.get(self.frame).copied()?;
+        self.frame += 1;
+
+        Some(actions)
+    }
+}
+
+// Replay error
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    Serialize(toml::ser::Error),
+    Parse(toml::de::Error),
+    Version(u32),
+    // File the replay depends on has changed since it was recorded
+    Changed(String),
+}
+
+// Display replay error
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "{e}"),
+            ReplayError::Serialize(e) => write!(f, "{e}"),
+            ReplayError::Parse(e) => write!(f, "{e}"),
+            ReplayError::Version(version) => write!(
+                f,
+                "replay version {version} is not supported (expected {REPLAY_VERSION})"
+            ),
+            ReplayError::Changed(path) => write!(
+                f,
+                "{path} has changed since the replay was recorded, so it would play differently"
+            ),
+        }
+    }
+}
+
+// Hash the contents of a file, or 0 if it's missing
+fn file_hash(path: impl AsRef<Path>) -> u64 {
+    std::fs::read(path).map_or(0, |bytes| text_hash(&bytes))
+}
+
+// Hash bytes with 64-bit FNV-1a, stable across builds unlike the std hasher
+fn text_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
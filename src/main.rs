@@ -1,10 +1,28 @@
+use editor::*;
 use game::*;
+use input::actions::*;
+use input::replay::*;
+use pause::*;
+use results::*;
 use settings::*;
+use simulation::*;
+use std::path::Path;
+use title::*;
+use world::loader::*;
+use world::maps::*;
+use world::native::*;
 
 mod components;
+mod editor;
 mod game;
+mod input;
+mod pause;
 mod physics;
+mod results;
+mod save;
+mod scores;
 mod settings;
+mod simulation;
 mod systems;
 mod title;
 mod world;
@@ -13,62 +31,399 @@ mod world;
 pub enum State {
     TitleScreen,
     Game(Box<Game>),
+    Editor(Box<Editor>),
+    Pause(PauseMenu),
+    Confirm(ConfirmDialog),
+    Results(ResultsScreen),
     Quit,
 }
 
+// State transitions
+pub enum Transition {
+    // Put a state on top of the current one (e.g. pause over the game)
+    Push(State),
+    // Remove the top state, going back to the one below
+    Pop,
+    // Replace the top state
+    Replace(State),
+    // Replace every state (e.g. leaving the game for the title screen)
+    Switch(State),
+}
+
+// Functions for Transition
+impl Transition {
+    // Apply transition to the state stack
+    pub fn apply(self, states: &mut Vec<State>) {
+        match self {
+            Transition::Push(state) => states.push(state),
+            Transition::Pop => {
+                states.pop();
+            }
+            Transition::Replace(state) => {
+                states.pop();
+                states.push(state);
+            }
+            Transition::Switch(state) => {
+                states.clear();
+                states.push(state);
+            }
+        }
+    }
+}
+
 fn main() {
+    // Load settings and apply command line overrides
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut settings = Settings::load_or_default(settings_path_from_args(&args));
+    let remaining_args = match settings.apply_args(&args) {
+        Ok(remaining_args) => remaining_args,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+    settings.activate();
+
+    // Check maps without opening a window, e.g. "--validate-map map_01.tmx"
+    if let Some(index) = remaining_args
+        .iter()
+        .position(|arg| arg == "--validate-map")
+    {
+        let filenames: Vec<String> = remaining_args[index + 1..]
+            .iter()
+            .take_while(|arg| !arg.starts_with("--"))
+            .cloned()
+            .collect();
+
+        // Debug logging would bury the results
+        settings.debug_mode = false;
+        settings.activate();
+
+        validate_maps(&filenames);
+        return;
+    }
+
+    // Convert maps to the native format, e.g. "--convert-map map_01.tmx"
+    if let Some(index) = remaining_args.iter().position(|arg| arg == "--convert-map") {
+        let filenames: Vec<String> = remaining_args[index + 1..]
+            .iter()
+            .take_while(|arg| !arg.starts_with("--"))
+            .cloned()
+            .collect();
+
+        convert_maps(&filenames);
+        return;
+    }
+
+    // Load replay to play back if requested
+    let replay = remaining_args
+        .iter()
+        .position(|arg| arg == "--replay")
+        .map(|index| match remaining_args.get(index + 1) {
+            Some(path) => Replay::read(path)
+                .and_then(|replay| replay.check_files(&settings).map(|()| replay))
+                .unwrap_or_else(|e| {
+                    eprintln!("Failed to load replay {path}: {e}");
+                    std::process::exit(1);
+                }),
+            None => {
+                eprintln!("missing or invalid value for --replay");
+                std::process::exit(2);
+            }
+        });
+
+    // Run without a window if requested (e.g. in CI)
+    if let Some(index) = remaining_args.iter().position(|arg| arg == "--headless") {
+        match replay {
+            Some(replay) => run_replay_headless(&settings, replay),
+            None => {
+                let steps = remaining_args
+                    .get(index + 1)
+                    .and_then(|arg| arg.parse().ok())
+                    .unwrap_or(HEADLESS_STEPS);
+
+                run_headless(&settings, steps);
+            }
+        }
+        return;
+    }
+
     // Create Raylib handle and thread
     let (mut rl, thread) = raylib::init()
-        .size(WINDOW_WIDTH, WINDOW_HEIGHT)
+        .size(settings.window_width, settings.window_height)
         .title("Witch Hunt")
         .build();
 
     // Set FPS
     rl.set_target_fps(60);
 
-    // Set state to title screen
-    let mut current_state = State::TitleScreen;
+    // Escape pauses the game instead of closing the window
+    rl.set_exit_key(None);
+
+    // Create title screen with discovered maps
+    let mut title_screen = TitleScreen::new(MapRegistry::discover_or_empty());
+
+    // Start on the title screen, or straight in the game when playing a replay
+    let first_state = match replay {
+        Some(replay) => match Game::from_replay(&settings, replay) {
+            Ok(game) => State::Game(Box::new(game)),
+            Err(e) => {
+                eprintln!("Failed to create game: {e}");
+                State::TitleScreen
+            }
+        },
+        None => State::TitleScreen,
+    };
+
+    // State stack, the top state is the one being played
+    let mut states = vec![first_state];
 
     // Main application loop
     while !rl.window_should_close() {
-        // Match current state
-        match current_state {
+        // Transition is None
+        let mut transition: Option<Transition> = None;
+
+        let Some((top, below)) = states.split_last_mut() else {
+            break;
+        };
+
+        // Update the state on top
+        match top {
             // State: Title Screen
-            State::TitleScreen => {
-                // Next state is None
-                let mut next_state: Option<State> = None;
+            State::TitleScreen => title_screen.update(&mut rl, &settings, &mut transition),
+            // State: Game
+            State::Game(game) => game.update(&mut rl, &thread, &mut transition),
+            // State: Editor
+            State::Editor(editor) => editor.update(&mut rl, &thread, &mut transition),
+            // State: Pause
+            State::Pause(pause_menu) => {
+                pause_menu.update(&mut rl, game_below(below), &mut transition)
+            }
+            // State: Confirm
+            State::Confirm(dialog) => dialog.update(&mut rl, &mut transition),
+            // State: Results
+            State::Results(results) => results.update(&mut rl),
+            // State: Quit
+            State::Quit => break,
+        }
 
-                // Update
-                title::update(&mut rl, &mut next_state);
+        // Render
+        {
+            // Begin drawing frame
+            let mut d = rl.begin_drawing(&thread);
 
-                // Render
-                title::render(&mut rl, &thread, &mut next_state);
+            // Draw states below first so overlays draw on top of the game or editor
+            for state in below.iter() {
+                match state {
+                    State::Game(game) => game.render(&mut d),
+                    State::Editor(editor) => editor.render(&mut d),
+                    _ => {}
+                }
+            }
 
-                // If next state has been set then change current state accordingly
-                if let Some(state) = next_state {
-                    current_state = state;
+            // Draw the state on top
+            match top {
+                State::TitleScreen => title_screen.render(&mut d, &settings, &mut transition),
+                State::Game(game) => game.render(&mut d),
+                State::Editor(editor) => editor.render(&mut d),
+                State::Pause(pause_menu) => {
+                    pause_menu.render(&mut d, game_below(below), &mut transition)
                 }
+                State::Confirm(dialog) => dialog.render(&mut d, game_below(below), &mut transition),
+                State::Results(results) => {
+                    results.render(&mut d, game_below(below), &mut transition)
+                }
+                State::Quit => {}
             }
-            // State: Game
-            State::Game(ref mut game) => {
-                // Next state is None
-                let mut next_state: Option<State> = None;
+        }
 
-                // Update
-                game.update(&mut rl, &mut next_state);
+        // If a transition has been set then change states accordingly
+        if let Some(transition) = transition {
+            transition.apply(&mut states);
+        }
+    }
+}
 
-                // Render
-                game.render(&mut rl, &thread);
+// Get the game under an overlay
+fn game_below(states: &mut [State]) -> Option<&mut Game> {
+    states.iter_mut().rev().find_map(|state| match state {
+        State::Game(game) => Some(game.as_mut()),
+        _ => None,
+    })
+}
 
-                // If next state has been set then change current state accordingly
-                if let Some(state) = next_state {
-                    current_state = state;
-                }
+// Check that maps load without problems, all discovered maps if none are given
+// Exits with an error code if any map has problems
+fn validate_maps(filenames: &[String]) {
+    let filenames: Vec<String> = if filenames.is_empty() {
+        MapRegistry::discover_or_empty()
+            .maps
+            .into_iter()
+            .map(|map| map.filename)
+            .collect()
+    } else {
+        filenames.to_vec()
+    };
+
+    if filenames.is_empty() {
+        eprintln!("No maps found in {MAPS_DIR}");
+        std::process::exit(1);
+    }
+
+    let mut failed = 0;
+
+    for filename in &filenames {
+        match load_map(filename) {
+            Ok(map) => println!(
+                "✅ {filename}: {}x{}x{} grid, {} objective(s), {} patrol route(s)",
+                map.grid.width,
+                map.grid.levels,
+                map.grid.height,
+                map.objectives.len(),
+                map.patrol_routes.len()
+            ),
+            Err(e @ MapError::Invalid { .. }) => {
+                failed += 1;
+                println!("❌ {e}");
             }
-            // State: Quit
-            State::Quit => {
-                break;
+            Err(e) => {
+                failed += 1;
+                println!("❌ {filename}: {e}");
             }
         }
     }
+
+    if failed > 0 {
+        eprintln!("{failed} of {} map(s) failed validation", filenames.len());
+        std::process::exit(1);
+    }
+}
+
+// Convert maps to native maps next to them, checking each reads back the same
+// Exits with an error code if any map fails to convert
+fn convert_maps(filenames: &[String]) {
+    if filenames.is_empty() {
+        eprintln!("missing value for --convert-map");
+        std::process::exit(2);
+    }
+
+    let mut failed = 0;
+
+    for filename in filenames {
+        let native_filename = Path::new(filename)
+            .with_extension(NATIVE_MAP_EXTENSION)
+            .to_string_lossy()
+            .into_owned();
+
+        let result = load_map(filename).and_then(|map| {
+            write_native_map(&native_filename, &map)?;
+
+            let written = load_native_map(&native_filename)?;
+            Ok(NativeMap::from_loaded(&map) == NativeMap::from_loaded(&written))
+        });
+
+        match result {
+            Ok(true) => println!("✅ {filename} -> {native_filename}"),
+            Ok(false) => {
+                failed += 1;
+                println!("❌ {filename}: {native_filename} doesn't read back the same");
+            }
+            Err(e) => {
+                failed += 1;
+                println!("❌ {filename}: {e}");
+            }
+        }
+    }
+
+    if failed > 0 {
+        eprintln!("{failed} of {} map(s) failed to convert", filenames.len());
+        std::process::exit(1);
+    }
+}
+
+// Run the simulation headless for a number of steps with no input
+fn run_headless(settings: &Settings, steps: u32) {
+    // Play the first map found
+    let maps = MapRegistry::discover_or_empty();
+    let Some(map) = maps.get(0) else {
+        eprintln!("No maps found in {MAPS_DIR}");
+        std::process::exit(1);
+    };
+
+    let seed = settings.seed_or_random();
+    println!("Simulating {} with seed {seed}.", map.name);
+
+    let mut simulation =
+        match Simulation::new(settings, &map.filename, PlayerStats::default(), seed) {
+            Ok(simulation) => simulation,
+            Err(e) => {
+                eprintln!("Failed to create simulation: {e}");
+                std::process::exit(1);
+            }
+        };
+
+    let actions = ActionState::default();
+
+    for step in 1..=steps {
+        match simulation.step(&actions) {
+            StepOutcome::Running => {}
+            StepOutcome::Lost(cause) => {
+                println!("GAME OVER! {} after {step} steps.", cause.description());
+                return;
+            }
+            StepOutcome::Won => {
+                println!("YOU WIN! Map cleared after {step} steps.");
+                return;
+            }
+        }
+    }
+
+    println!("Simulated {steps} steps.");
+}
+
+// Play a replay back headless and report how it ended
+fn run_replay_headless(settings: &Settings, replay: Replay) {
+    let settings = replay.settings(settings);
+
+    println!(
+        "Replaying {} with seed {} ({} steps).",
+        replay.map.name,
+        replay.seed(),
+        replay.frames.len()
+    );
+
+    let mut simulation = match Simulation::new(
+        &settings,
+        &replay.map.filename,
+        PlayerStats::default(),
+        replay.seed(),
+    ) {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            eprintln!("Failed to create simulation: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut playback = ReplayPlayback::new(replay);
+
+    while let Some(actions) = playback.next_actions() {
+        match simulation.step(&actions) {
+            StepOutcome::Running => {}
+            StepOutcome::Lost(cause) => {
+                println!(
+                    "GAME OVER! {} after {} steps.",
+                    cause.description(),
+                    playback.frame
+                );
+                return;
+            }
+            StepOutcome::Won => {
+                println!("YOU WIN! Map cleared after {} steps.", playback.frame);
+                return;
+            }
+        }
+    }
+
+    println!("Replay finished after {} steps.", playback.frame);
 }
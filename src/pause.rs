@@ -0,0 +1,228 @@
+use crate::game::*;
+use crate::input::actions::*;
+use crate::{State, Transition};
+use raylib::prelude::*;
+
+// Mouse sensitivity range and step in the settings panel
+const MIN_MOUSE_SENSITIVITY: f32 = 0.0005;
+const MAX_MOUSE_SENSITIVITY: f32 = 0.02;
+const MOUSE_SENSITIVITY_STEP: f32 = 0.0005;
+
+// Pause menu
+// Drawn over the game, which is frozen until the menu is closed
+pub struct PauseMenu {
+    pub showing_settings: bool,
+}
+
+// Functions for PauseMenu
+impl PauseMenu {
+    pub fn new() -> Self {
+        Self {
+            showing_settings: false,
+        }
+    }
+
+    // Update
+    pub fn update(
+        &mut self,
+        rl: &mut RaylibHandle,
+        game: Option<&mut Game>,
+        transition: &mut Option<Transition>,
+    ) {
+        // If cursor is hidden then enable it
+        if rl.is_cursor_hidden() {
+            rl.enable_cursor();
+        }
+
+        // Set text style for GUI
+        rl.gui_set_style(GuiControl::DEFAULT, GuiDefaultProperty::TEXT_SIZE, 20);
+
+        // Press Pause again to resume
+        if let Some(game) = game
+            && game.input_map.read(rl).is_pressed(Action::Pause)
+        {
+            *transition = Some(Transition::Pop);
+        }
+    }
+
+    // Render
+    pub fn render(
+        &mut self,
+        d: &mut RaylibDrawHandle,
+        game: Option<&mut Game>,
+        transition: &mut Option<Transition>,
+    ) {
+        // Dim the game underneath
+        draw_overlay(d, "PAUSED");
+
+        let x = (d.get_screen_width() as f32 - 200.0) / 2.0;
+
+        // Settings panel
+        if self.showing_settings {
+            if let Some(game) = game {
+                self.render_settings(d, game, x);
+            }
+            return;
+        }
+
+        // Resume button
+        if d.gui_button(Rectangle::new(x, 120.0, 200.0, 40.0), "RESUME") {
+            *transition = Some(Transition::Pop);
+        }
+
+        // Settings button
+        if d.gui_button(Rectangle::new(x, 170.0, 200.0, 40.0), "SETTINGS") {
+            self.showing_settings = true;
+        }
+
+        // Restart button
+        if d.gui_button(Rectangle::new(x, 220.0, 200.0, 40.0), "RESTART") {
+            let dialog = ConfirmDialog::new(ConfirmAction::Restart);
+            *transition = Some(Transition::Replace(State::Confirm(dialog)));
+        }
+
+        // Quit to title button
+        if d.gui_button(Rectangle::new(x, 270.0, 200.0, 40.0), "QUIT TO TITLE") {
+            let dialog = ConfirmDialog::new(ConfirmAction::QuitToTitle);
+            *transition = Some(Transition::Replace(State::Confirm(dialog)));
+        }
+    }
+
+    // Render settings panel
+    fn render_settings(&mut self, d: &mut RaylibDrawHandle, game: &mut Game, x: f32) {
+        // Mouse sensitivity
+        let sensitivity = game.settings.mouse_sensitivity;
+        d.gui_label(
+            Rectangle::new(x - 50.0, 120.0, 300.0, 40.0),
+            &format!("Mouse sensitivity: {sensitivity:.4}"),
+        );
+
+        if d.gui_button(Rectangle::new(x, 170.0, 95.0, 40.0), "-") {
+            game.set_mouse_sensitivity(
+                (sensitivity - MOUSE_SENSITIVITY_STEP).max(MIN_MOUSE_SENSITIVITY),
+            );
+        }
+        if d.gui_button(Rectangle::new(x + 105.0, 170.0, 95.0, 40.0), "+") {
+            game.set_mouse_sensitivity(
+                (sensitivity + MOUSE_SENSITIVITY_STEP).min(MAX_MOUSE_SENSITIVITY),
+            );
+        }
+
+        // Debug mode
+        let debug_label = if game.settings.debug_mode {
+            "DEBUG: ON"
+        } else {
+            "DEBUG: OFF"
+        };
+        if d.gui_button(Rectangle::new(x, 220.0, 200.0, 40.0), debug_label) {
+            game.set_debug_mode(!game.settings.debug_mode);
+        }
+
+        // Back button
+        if d.gui_button(Rectangle::new(x, 270.0, 200.0, 40.0), "BACK") {
+            self.showing_settings = false;
+        }
+    }
+}
+
+// Action waiting on confirmation
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConfirmAction {
+    Restart,
+    QuitToTitle,
+    DiscardEdits,
+}
+
+// Confirmation dialog
+// Asked before abandoning a run or unsaved edits
+pub struct ConfirmDialog {
+    pub action: ConfirmAction,
+}
+
+// Functions for ConfirmDialog
+impl ConfirmDialog {
+    pub fn new(action: ConfirmAction) -> Self {
+        Self { action }
+    }
+
+    // Update
+    pub fn update(&mut self, rl: &mut RaylibHandle, transition: &mut Option<Transition>) {
+        // If cursor is hidden then enable it
+        if rl.is_cursor_hidden() {
+            rl.enable_cursor();
+        }
+
+        // Set text style for GUI
+        rl.gui_set_style(GuiControl::DEFAULT, GuiDefaultProperty::TEXT_SIZE, 20);
+
+        // Press Escape to go back to where the dialog was opened from
+        if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+            *transition = Some(self.back());
+        }
+    }
+
+    // Render
+    pub fn render(
+        &mut self,
+        d: &mut RaylibDrawHandle,
+        game: Option<&mut Game>,
+        transition: &mut Option<Transition>,
+    ) {
+        let (message, warning) = match self.action {
+            ConfirmAction::Restart => ("Restart this map?", "Progress on this run will be lost."),
+            ConfirmAction::QuitToTitle => (
+                "Quit to the title screen?",
+                "Progress on this run will be lost.",
+            ),
+            ConfirmAction::DiscardEdits => (
+                "Leave the editor?",
+                "Unsaved changes to this map will be lost.",
+            ),
+        };
+
+        // Dim the game underneath
+        draw_overlay(d, message);
+
+        let x = (d.get_screen_width() as f32 - 200.0) / 2.0;
+
+        d.gui_label(Rectangle::new(x - 50.0, 120.0, 300.0, 40.0), warning);
+
+        // Yes button
+        if d.gui_button(Rectangle::new(x, 170.0, 95.0, 40.0), "YES") {
+            *transition = Some(match (self.action, game) {
+                (ConfirmAction::Restart, Some(game)) => match game.restart() {
+                    Ok(game) => Transition::Switch(State::Game(Box::new(game))),
+                    Err(e) => {
+                        eprintln!("Failed to restart game: {e}");
+                        Transition::Switch(State::TitleScreen)
+                    }
+                },
+                _ => Transition::Switch(State::TitleScreen),
+            });
+        }
+
+        // No button
+        if d.gui_button(Rectangle::new(x + 105.0, 170.0, 95.0, 40.0), "NO") {
+            *transition = Some(self.back());
+        }
+    }
+
+    // Go back to the pause menu, or to the editor underneath
+    fn back(&self) -> Transition {
+        match self.action {
+            ConfirmAction::DiscardEdits => Transition::Pop,
+            _ => Transition::Replace(State::Pause(PauseMenu::new())),
+        }
+    }
+}
+
+// Dim the screen and draw a heading
+pub fn draw_overlay(d: &mut RaylibDrawHandle, heading: &str) {
+    let width = d.get_screen_width();
+    let height = d.get_screen_height();
+
+    d.draw_rectangle(0, 0, width, height, Color::new(0, 0, 0, 150));
+
+    let text_width = d.measure_text(heading, 30);
+    d.draw_text(heading, (width - text_width) / 2, 60, 30, Color::WHITE);
+}
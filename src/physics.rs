@@ -1,4 +1,6 @@
+use rapier3d::crossbeam::channel::{unbounded, Receiver};
 use rapier3d::prelude::*;
+use std::collections::HashMap;
 
 // Physics World
 pub struct PhysicsWorld {
@@ -14,12 +16,20 @@ pub struct PhysicsWorld {
     multibody_joints: MultibodyJointSet,
     ccd_solver: CCDSolver,
     pub query_pipeline: QueryPipeline,
+    previous_translations: HashMap<RigidBodyHandle, Vector<f32>>,
+    event_collector: ChannelEventCollector,
+    collision_events: Receiver<CollisionEvent>,
+    contact_force_events: Receiver<ContactForceEvent>,
 }
 
 // Functions for physics world
 impl PhysicsWorld {
     // Create a new physics world
     pub fn new() -> Self {
+        // Create channels for collision and contact force events
+        let (collision_send, collision_events) = unbounded();
+        let (contact_force_send, contact_force_events) = unbounded();
+
         Self {
             pipeline: PhysicsPipeline::new(),
             gravity: vector![0.0, -9.81, 0.0],
@@ -33,11 +43,49 @@ impl PhysicsWorld {
             multibody_joints: MultibodyJointSet::new(),
             ccd_solver: CCDSolver::new(),
             query_pipeline: QueryPipeline::new(),
+            previous_translations: HashMap::new(),
+            event_collector: ChannelEventCollector::new(collision_send, contact_force_send),
+            collision_events,
+            contact_force_events,
+        }
+    }
+
+    // Time covered by a single step in seconds
+    pub fn timestep(&self) -> f32 {
+        self.integration_params.dt
+    }
+
+    // Set time covered by a single step in seconds
+    pub fn set_timestep(&mut self, dt: f32) {
+        self.integration_params.dt = dt;
+    }
+
+    // Body position between the last two steps
+    // Alpha is how far through the next step the frame is (0.0 to 1.0)
+    pub fn interpolated_translation(
+        &self,
+        body_handle: RigidBodyHandle,
+        alpha: f32,
+    ) -> Option<Vector<f32>> {
+        let current = *self.bodies.get(body_handle)?.translation();
+
+        match self.previous_translations.get(&body_handle) {
+            Some(previous) => Some(previous.lerp(&current, alpha)),
+            None => Some(current),
         }
     }
 
     // Step the physics world forward
     pub fn step(&mut self) {
+        // Remember where moving bodies were for interpolation
+        self.previous_translations.clear();
+        for (body_handle, body) in self.bodies.iter() {
+            if !body.is_fixed() {
+                self.previous_translations
+                    .insert(body_handle, *body.translation());
+            }
+        }
+
         self.pipeline.step(
             &self.gravity,
             &self.integration_params,
@@ -49,11 +97,41 @@ impl PhysicsWorld {
             &mut self.impulse_joints,
             &mut self.multibody_joints,
             &mut self.ccd_solver,
-            None, // Optional QueryPipeline
-            &(),  // Hooks
-            &(),  // Event handler
+            None,                  // Optional QueryPipeline
+            &(),                   // Hooks
+            &self.event_collector, // Event handler
         );
 
         self.query_pipeline.update(&self.colliders);
     }
+
+    // Take collision events from the last steps
+    pub fn drain_collision_events(&self) -> impl Iterator<Item = CollisionEvent> + '_ {
+        self.collision_events.try_iter()
+    }
+
+    // Take contact force events from the last steps
+    pub fn drain_contact_force_events(&self) -> impl Iterator<Item = ContactForceEvent> + '_ {
+        self.contact_force_events.try_iter()
+    }
+
+    // Check if two colliders are touching as of the last step
+    pub fn colliders_touching(&self, a: ColliderHandle, b: ColliderHandle) -> bool {
+        self.narrow_phase
+            .contact_pair(a, b)
+            .is_some_and(|pair| pair.has_any_active_contact)
+    }
+
+    // Remove a body and its colliders
+    pub fn remove_body(&mut self, body_handle: RigidBodyHandle) {
+        self.bodies.remove(
+            body_handle,
+            &mut self.island_manager,
+            &mut self.colliders,
+            &mut self.impulse_joints,
+            &mut self.multibody_joints,
+            true,
+        );
+        self.previous_translations.remove(&body_handle);
+    }
 }
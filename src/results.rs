@@ -0,0 +1,151 @@
+use crate::game::*;
+use crate::pause::*;
+use crate::scores::*;
+use crate::simulation::*;
+use crate::{State, Transition};
+use raylib::prelude::*;
+
+// How a run ended
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RunOutcome {
+    Won,
+    Lost(LossCause),
+}
+
+// Run summary
+// Shown on the results screen and kept in the high score table
+#[derive(Clone, Debug)]
+pub struct RunSummary {
+    pub outcome: RunOutcome,
+    pub label: String,
+    pub seed: u64,
+    pub stats: PlayerStats,
+}
+
+// Results screen
+// Drawn over the finished game until the player retries or goes back to the title
+pub struct ResultsScreen {
+    pub summary: RunSummary,
+    pub high_scores: HighScores,
+    // Place of this run in the high score table, if it made it
+    pub rank: Option<usize>,
+}
+
+// Functions for ResultsScreen
+impl ResultsScreen {
+    // Show results of a run, adding it to the high score table if it counts
+    pub fn new(summary: RunSummary, record: bool) -> Self {
+        let mut high_scores = HighScores::load_or_default(HIGH_SCORES_PATH);
+        let mut rank = None;
+
+        if record {
+            rank = high_scores.insert(HighScore {
+                label: summary.label.clone(),
+                seed: summary.seed as i64,
+                won: summary.outcome == RunOutcome::Won,
+                stats: summary.stats,
+            });
+
+            if rank.is_some()
+                && let Err(e) = high_scores.write(HIGH_SCORES_PATH)
+            {
+                eprintln!("Failed to save high scores: {e}");
+            }
+        }
+
+        Self {
+            summary,
+            high_scores,
+            rank,
+        }
+    }
+
+    // Update
+    pub fn update(&mut self, rl: &mut RaylibHandle) {
+        // If cursor is hidden then enable it
+        if rl.is_cursor_hidden() {
+            rl.enable_cursor();
+        }
+
+        // Set text style for GUI
+        rl.gui_set_style(GuiControl::DEFAULT, GuiDefaultProperty::TEXT_SIZE, 20);
+    }
+
+    // Render
+    pub fn render(
+        &mut self,
+        d: &mut RaylibDrawHandle,
+        game: Option<&mut Game>,
+        transition: &mut Option<Transition>,
+    ) {
+        let heading = match self.summary.outcome {
+            RunOutcome::Won => "YOU WIN!",
+            RunOutcome::Lost(_) => "GAME OVER",
+        };
+
+        // Dim the game underneath
+        draw_overlay(d, heading);
+
+        // Run statistics
+        let stats = &self.summary.stats;
+        let result = match self.summary.outcome {
+            RunOutcome::Won => "All maps cleared",
+            RunOutcome::Lost(cause) => cause.description(),
+        };
+        let lines = [
+            result.to_string(),
+            format!("Map: {}", self.summary.label),
+            format!("Seed: {}", self.summary.seed),
+            format!("Time survived: {:.1}s", stats.time_survived),
+            format!("Distance travelled: {:.1}m", stats.distance_travelled),
+            format!("Times spotted: {}", stats.times_spotted),
+            format!("Maps cleared: {}", stats.maps_completed),
+        ];
+
+        for (i, line) in lines.iter().enumerate() {
+            d.draw_text(line, 80, 110 + i as i32 * 28, 20, Color::WHITE);
+        }
+
+        // High score table
+        d.draw_text("HIGH SCORES", 460, 110, 20, Color::WHITE);
+
+        for (i, score) in self.high_scores.scores.iter().enumerate() {
+            let color = if self.rank == Some(i) {
+                Color::YELLOW
+            } else {
+                Color::LIGHTGRAY
+            };
+
+            d.draw_text(
+                &format!(
+                    "{:>2}. {:<12} {} maps {:.1}s",
+                    i + 1,
+                    score.label,
+                    score.stats.maps_completed,
+                    score.stats.time_survived
+                ),
+                460,
+                140 + i as i32 * 18,
+                15,
+                color,
+            );
+        }
+
+        // Retry button
+        if d.gui_button(Rectangle::new(80.0, 330.0, 200.0, 40.0), "RETRY") {
+            *transition = Some(match game.map(|game| game.retry()) {
+                Some(Ok(game)) => Transition::Switch(State::Game(Box::new(game))),
+                Some(Err(e)) => {
+                    eprintln!("Failed to create game: {e}");
+                    Transition::Switch(State::TitleScreen)
+                }
+                None => Transition::Switch(State::TitleScreen),
+            });
+        }
+
+        // Main menu button
+        if d.gui_button(Rectangle::new(290.0, 330.0, 200.0, 40.0), "MAIN MENU") {
+            *transition = Some(Transition::Switch(State::TitleScreen));
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::components::*;
+use crate::game::GameMode;
+use crate::simulation::PlayerStats;
+use crate::systems::objectives::Objectives;
+use crate::world::grid::Grid;
+use crate::world::patrol::PatrolRoute;
+use rapier3d::na::{Quaternion, Translation3, UnitQuaternion};
+use rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+// Save file format version
+// Bump when the layout changes so old saves are rejected instead of misread
+pub const SAVE_VERSION: u32 = 9;
+
+// Quick save file
+pub const QUICKSAVE_PATH: &str = "saves/quicksave.toml";
+
+// Save game
+// Everything needed to restore a Game exactly where it was left
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub mode: GameMode,
+    pub start_stats: PlayerStats,
+    pub camera: CameraState,
+    pub simulation: SimulationState,
+}
+
+// Camera position and orientation
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct CameraState {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    pub up: [f32; 3],
+    pub fovy: f32,
+}
+
+// Simulation state
+#[derive(Serialize, Deserialize)]
+pub struct SimulationState {
+    // Seeds are stored as i64 bit patterns since TOML integers are signed
+    pub seed: i64,
+    pub rng_seed: i64,
+    pub stats: PlayerStats,
+    pub map_time: f32,
+    pub objectives: Objectives,
+    pub patrol_routes: Vec<PatrolRoute>,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub grid: Grid,
+    pub players: Vec<SavedEntity<Player>>,
+    pub trees: Vec<SavedEntity<Tree>>,
+    pub balls: Vec<SavedEntity<crate::components::Ball>>,
+    pub items: Vec<SavedEntity<Item>>,
+    pub witches: Vec<SavedEntity<SavedWitch>>,
+}
+
+// Saved witch
+// Collider handle and path are rebuilt on load
+#[derive(Serialize, Deserialize)]
+pub struct SavedWitch {
+    pub color: Rgba,
+    pub state: WitchState,
+    pub state_time: f32,
+    pub target: [f32; 3],
+    pub route: Option<usize>,
+    pub waypoint: usize,
+    pub home: [f32; 3],
+    pub facing: [f32; 3],
+    pub last_known_position: Option<[f32; 3]>,
+}
+
+// Saved entity: its component and the state of its rigid body
+#[derive(Serialize, Deserialize)]
+pub struct SavedEntity<C> {
+    pub component: C,
+    pub body: BodyState,
+}
+
+// Rigid body position and velocity
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct BodyState {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub linvel: [f32; 3],
+    pub angvel: [f32; 3],
+}
+
+// Functions for BodyState
+impl BodyState {
+    // Capture state of a rigid body
+    pub fn capture(body: &RigidBody) -> Self {
+        let position = body.position();
+        let rotation = position.rotation.quaternion();
+
+        Self {
+            translation: position.translation.vector.into(),
+            rotation: [rotation.i, rotation.j, rotation.k, rotation.w],
+            linvel: (*body.linvel()).into(),
+            angvel: (*body.angvel()).into(),
+        }
+    }
+
+    // Translation as a Rapier3D Vector
+    pub fn translation(&self) -> Vector<f32> {
+        Vector::from(self.translation)
+    }
+
+    // Apply state to a rigid body
+    pub fn apply(&self, body: &mut RigidBody) {
+        let [i, j, k, w] = self.rotation;
+        let rotation = UnitQuaternion::from_quaternion(Quaternion::new(w, i, j, k));
+
+        body.set_position(
+            Isometry::from_parts(Translation3::from(self.translation()), rotation),
+            true,
+        );
+        body.set_linvel(Vector::from(self.linvel), true);
+        body.set_angvel(Vector::from(self.angvel), true);
+    }
+}
+
+// Functions for SaveGame
+impl SaveGame {
+    // Write save game to file, creating its directory if needed
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let path = path.as_ref();
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(SaveError::Io)?;
+        }
+
+        let text = toml::to_string(self).map_err(SaveError::Serialize)?;
+
+        std::fs::write(path, text).map_err(SaveError::Io)
+    }
+
+    // Read save game from file
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        let text = std::fs::read_to_string(path).map_err(SaveError::Io)?;
+
+        // Check version before reading the rest so old layouts give a clear error
+        #[derive(Deserialize)]
+        struct Header {
+            version: u32,
+        }
+
+        let header: Header = toml::from_str(&text).map_err(SaveError::Parse)?;
+        if header.version != SAVE_VERSION {
+            return Err(SaveError::Version(header.version));
+        }
+
+        toml::from_str(&text).map_err(SaveError::Parse)
+    }
+}
+
+// Is there a quick save to continue from
+pub fn quicksave_exists() -> bool {
+    Path::new(QUICKSAVE_PATH).is_file()
+}
+
+// Save error
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Serialize(toml::ser::Error),
+    Parse(toml::de::Error),
+    Version(u32),
+}
+
+// Display save error
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "{e}"),
+            SaveError::Serialize(e) => write!(f, "{e}"),
+            SaveError::Parse(e) => write!(f, "{e}"),
+            SaveError::Version(version) => write!(
+                f,
+                "save version {version} is not supported (expected {SAVE_VERSION})"
+            ),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use crate::simulation::PlayerStats;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::Path;
+
+// High score file format version
+// Bump when the layout changes so old tables are rejected instead of misread
+pub const HIGH_SCORES_VERSION: u32 = 1;
+
+// High score table file
+pub const HIGH_SCORES_PATH: &str = "saves/highscores.toml";
+
+// Number of runs kept in the table
+pub const MAX_HIGH_SCORES: usize = 10;
+
+// High score
+// One finished run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HighScore {
+    pub label: String,
+    // Stored as an i64 bit pattern since TOML integers are signed
+    pub seed: i64,
+    pub won: bool,
+    pub stats: PlayerStats,
+}
+
+// Functions for HighScore
+impl HighScore {
+    // Order runs best first: most maps completed, then longest time survived
+    fn rank(&self, other: &Self) -> Ordering {
+        other
+            .stats
+            .maps_completed
+            .cmp(&self.stats.maps_completed)
+            .then(
+                other
+                    .stats
+                    .time_survived
+                    .total_cmp(&self.stats.time_survived),
+            )
+    }
+}
+
+// High score table
+// Best runs on this machine, best first
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HighScores {
+    pub version: u32,
+    pub scores: Vec<HighScore>,
+}
+
+// Default high score table
+impl Default for HighScores {
+    fn default() -> Self {
+        Self {
+            version: HIGH_SCORES_VERSION,
+            scores: Vec::new(),
+        }
+    }
+}
+
+// Functions for HighScores
+impl HighScores {
+    // Add a run to the table
+    // Returns its place (0 is best), or None if it didn't make the table
+    pub fn insert(&mut self, score: HighScore) -> Option<usize> {
+        let index = self
+            .scores
+            .iter()
+            .position(|existing| score.rank(existing) == Ordering::Less)
+            .unwrap_or(self.scores.len());
+
+        if index >= MAX_HIGH_SCORES {
+            return None;
+        }
+
+        self.scores.insert(index, score);
+        self.scores.truncate(MAX_HIGH_SCORES);
+
+        Some(index)
+    }
+
+    // Write high scores to file, creating its directory if needed
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), ScoreError> {
+        let path = path.as_ref();
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(ScoreError::Io)?;
+        }
+
+        let text = toml::to_string(self).map_err(ScoreError::Serialize)?;
+
+        std::fs::write(path, text).map_err(ScoreError::Io)
+    }
+
+    // Read high scores from file
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, ScoreError> {
+        let text = std::fs::read_to_string(path).map_err(ScoreError::Io)?;
+
+        // Check version before reading the rest so old layouts give a clear error
+        #[derive(Deserialize)]
+        struct Header {
+            version: u32,
+        }
+
+        let header: Header = toml::from_str(&text).map_err(ScoreError::Parse)?;
+        if header.version != HIGH_SCORES_VERSION {
+            return Err(ScoreError::Version(header.version));
+        }
+
+        toml::from_str(&text).map_err(ScoreError::Parse)
+    }
+
+    // Read high scores, or start an empty table if there are none yet
+    pub fn load_or_default(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+
+        if !path.is_file() {
+            return Self::default();
+        }
+
+        match Self::read(path) {
+            Ok(high_scores) => high_scores,
+            Err(e) => {
+                eprintln!("Failed to load high scores from {}: {e}", path.display());
+                Self::default()
+            }
+        }
+    }
+}
+
+// High score error
+#[derive(Debug)]
+pub enum ScoreError {
+    Io(std::io::Error),
+    Serialize(toml::ser::Error),
+    Parse(toml::de::Error),
+    Version(u32),
+}
+
+// Display high score error
+impl fmt::Display for ScoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScoreError::Io(e) => write!(f, "{e}"),
+            ScoreError::Serialize(e) => write!(f, "{e}"),
+            ScoreError::Parse(e) => write!(f, "{e}"),
+            ScoreError::Version(version) => write!(
+                f,
+                "high score version {version} is not supported (expected {HIGH_SCORES_VERSION})"
+            ),
+        }
+    }
+}
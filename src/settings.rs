@@ -1,18 +1,248 @@
-// Debug mode
-pub const DEBUG_MODE: bool = true;
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-// Window size
-pub const WINDOW_WIDTH: i32 = 800;
-pub const WINDOW_HEIGHT: i32 = 450;
+// Settings config file
+pub const SETTINGS_PATH: &str = "assets/config/settings.toml";
 
-// Mouse sensitivity
-pub const MOUSE_SENSITIVITY: f32 = 0.003;
+// Steps to run in headless mode if none given
+pub const HEADLESS_STEPS: u32 = 600;
 
-// Tree count
-pub const NUM_OF_TREES: u32 = 25;
+// Debug mode flag shared by debug logging
+static DEBUG_MODE: AtomicBool = AtomicBool::new(true);
 
-// Ball count
-pub const NUM_OF_BALLS: u32 = 4;
+// Is debug mode on
+pub fn debug_mode() -> bool {
+    DEBUG_MODE.load(Ordering::Relaxed)
+}
 
-// Witch count
-pub const NUM_OF_WITCHES: u32 = 1;
+// Settings
+// Loaded from the settings config file with command line overrides on top
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    // Debug mode
+    pub debug_mode: bool,
+
+    // Window size
+    pub window_width: i32,
+    pub window_height: i32,
+
+    // Input bindings config file
+    pub input_config: String,
+
+    // Witch behaviour config file
+    pub witch_config: String,
+
+    // Mouse sensitivity
+    pub mouse_sensitivity: f32,
+
+    // Tree count
+    pub num_of_trees: u32,
+
+    // Ball count
+    pub num_of_balls: u32,
+
+    // Witch count
+    pub num_of_witches: u32,
+
+    // Seconds to survive to clear a map
+    pub survive_seconds: f32,
+
+    // Physics steps per second, independent of frame rate
+    pub physics_hz: f32,
+
+    // World generation seed, random if not set
+    pub seed: Option<u64>,
+}
+
+// Default settings
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            debug_mode: true,
+            window_width: 800,
+            window_height: 450,
+            input_config: "assets/config/input.toml".to_string(),
+            witch_config: "assets/config/witches.toml".to_string(),
+            mouse_sensitivity: 0.003,
+            num_of_trees: 25,
+            num_of_balls: 4,
+            num_of_witches: 1,
+            survive_seconds: 60.0,
+            physics_hz: 60.0,
+            seed: None,
+        }
+    }
+}
+
+// Functions for Settings
+impl Settings {
+    // Load settings from config file
+    // Settings missing from the file keep their default values
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SettingsError> {
+        let text = std::fs::read_to_string(path).map_err(SettingsError::Io)?;
+        let settings: Self = toml::from_str(&text).map_err(SettingsError::Parse)?;
+
+        // A zero or negative physics rate would stall or hang the fixed step loop
+        if !is_valid_physics_hz(settings.physics_hz) {
+            return Err(SettingsError::InvalidArgument("physics_hz".to_string()));
+        }
+
+        Ok(settings)
+    }
+
+    // Load settings from config file or fall back to defaults
+    pub fn load_or_default(path: impl AsRef<Path>) -> Self {
+        match Self::load(&path) {
+            Ok(settings) => settings,
+            Err(e) => {
+                eprintln!(
+                    "Failed to load settings {}: {e}. Using default settings.",
+                    path.as_ref().display()
+                );
+                Self::default()
+            }
+        }
+    }
+
+    // Apply command line overrides, e.g. "--trees 50 --no-debug"
+    // Returns any arguments that aren't settings for the caller to handle
+    pub fn apply_args(&mut self, args: &[String]) -> Result<Vec<String>, SettingsError> {
+        let mut remaining = Vec::new();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--debug" => self.debug_mode = true,
+                "--no-debug" => self.debug_mode = false,
+                "--width" => self.window_width = parse_value(arg, args.next())?,
+                "--height" => self.window_height = parse_value(arg, args.next())?,
+                "--input" => self.input_config = parse_value(arg, args.next())?,
+                "--witch-config" => self.witch_config = parse_value(arg, args.next())?,
+                "--sensitivity" => self.mouse_sensitivity = parse_value(arg, args.next())?,
+                "--trees" => self.num_of_trees = parse_value(arg, args.next())?,
+                "--balls" => self.num_of_balls = parse_value(arg, args.next())?,
+                "--witches" => self.num_of_witches = parse_value(arg, args.next())?,
+                "--survive" => self.survive_seconds = parse_value(arg, args.next())?,
+                "--physics-hz" => {
+                    self.physics_hz = parse_value(arg, args.next())?;
+
+                    if !is_valid_physics_hz(self.physics_hz) {
+                        return Err(SettingsError::InvalidArgument(arg.clone()));
+                    }
+                }
+                "--seed" => self.seed = Some(parse_value(arg, args.next())?),
+                // Already used to find the settings file
+                "--settings" => {
+                    args.next();
+                }
+                _ => remaining.push(arg.clone()),
+            }
+        }
+
+        Ok(remaining)
+    }
+
+    // Get the configured seed or pick a random one
+    pub fn seed_or_random(&self) -> u64 {
+        self.seed.unwrap_or_else(rand::random)
+    }
+
+    // Make these settings the active ones for global flags (e.g. debug logging)
+    pub fn activate(&self) {
+        DEBUG_MODE.store(self.debug_mode, Ordering::Relaxed);
+    }
+}
+
+// Get settings file path from command line or use the default
+pub fn settings_path_from_args(args: &[String]) -> String {
+    args.iter()
+        .position(|arg| arg == "--settings")
+        .and_then(|index| args.get(index + 1))
+        .cloned()
+        .unwrap_or_else(|| SETTINGS_PATH.to_string())
+}
+
+// Physics rate must be a positive number of steps per second
+fn is_valid_physics_hz(physics_hz: f32) -> bool {
+    physics_hz.is_finite() && physics_hz > 0.0
+}
+
+// Parse the value following a command line flag
+fn parse_value<T: std::str::FromStr>(
+    flag: &str,
+    value: Option<&String>,
+) -> Result<T, SettingsError> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| SettingsError::InvalidArgument(flag.to_string()))
+}
+
+// Settings error
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    InvalidArgument(String),
+}
+
+// Display settings error
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Io(e) => write!(f, "{e}"),
+            SettingsError::Parse(e) => write!(f, "{e}"),
+            SettingsError::InvalidArgument(flag) => {
+                write!(f, "missing or invalid value for {flag}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn apply_args_sets_values_and_returns_the_rest() {
+        let mut settings = Settings::default();
+
+        let remaining = settings
+            .apply_args(&args(&["--trees", "50", "--no-debug", "--headless", "10"]))
+            .unwrap();
+
+        assert_eq!(settings.num_of_trees, 50);
+        assert!(!settings.debug_mode);
+        assert_eq!(remaining, args(&["--headless", "10"]));
+    }
+
+    #[test]
+    fn apply_args_rejects_missing_values() {
+        let mut settings = Settings::default();
+
+        assert!(matches!(
+            settings.apply_args(&args(&["--trees"])),
+            Err(SettingsError::InvalidArgument(flag)) if flag == "--trees"
+        ));
+    }
+
+    #[test]
+    fn apply_args_rejects_bad_physics_rates() {
+        for value in ["0", "-30", "inf", "NaN"] {
+            let mut settings = Settings::default();
+
+            assert!(
+                settings
+                    .apply_args(&args(&["--physics-hz", value]))
+                    .is_err(),
+                "--physics-hz {value} should be rejected"
+            );
+        }
+    }
+}
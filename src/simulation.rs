@@ -0,0 +1,606 @@
+use crate::components::*;
+use crate::input::actions::*;
+use crate::physics::*;
+use crate::save::*;
+use crate::settings::*;
+use crate::systems::ai::*;
+use crate::systems::behaviour::*;
+use crate::systems::events::*;
+use crate::systems::objectives::*;
+use crate::systems::player::*;
+use crate::systems::spawn::*;
+use crate::systems::terrain::*;
+use crate::world::grid::*;
+use crate::world::loader::*;
+use crate::world::navigation::*;
+use crate::world::patrol::*;
+use hecs::World;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Player stats
+// Carried over between maps in a campaign
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub time_survived: f32,
+    pub distance_travelled: f32,
+    pub times_spotted: u32,
+    pub maps_completed: u32,
+}
+
+// Outcome of a simulation step
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StepOutcome {
+    Running,
+    Lost(LossCause),
+    Won,
+}
+
+// Why a run was lost
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LossCause {
+    CaughtByWitch,
+}
+
+// Functions for LossCause
+impl LossCause {
+    // Description for the results screen
+    pub fn description(&self) -> &'static str {
+        match self {
+            LossCause::CaughtByWitch => "Caught by a witch",
+        }
+    }
+}
+
+// Simulation
+// Headless game world: ECS, physics, grid, and AI with no window or renderer
+pub struct Simulation {
+    pub ecs_world: World,
+    pub physics_world: PhysicsWorld,
+    pub grid: Grid,
+    pub terrain: Terrain,
+    pub nav_grid: NavGrid,
+    pub mouse_look: MouseLook,
+    pub stats: PlayerStats,
+    pub map_time: f32,
+    pub objectives: Objectives,
+    pub patrol_routes: Vec<PatrolRoute>,
+    pub seed: u64,
+    pub rng: StdRng,
+    pub events: EventQueue,
+    pub behaviour: WitchBehaviour,
+    // Tiles changed during the last step, for anything built from the grid outside the simulation
+    pub changed_tiles: Vec<GridCoord>,
+}
+
+// Functions for Simulation
+impl Simulation {
+    // Create a new simulation on a map, starting from existing player stats
+    // The same seed and map always generate the same world
+    pub fn new(
+        settings: &Settings,
+        map_filename: &str,
+        stats: PlayerStats,
+        seed: u64,
+    ) -> Result<Self, MapError> {
+        // Create RNG shared by spawners and AI
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        // Create mouse look
+        let mouse_look = MouseLook::new(settings.mouse_sensitivity);
+
+        // Create ECS world
+        let mut ecs_world = World::new();
+
+        // Create physics world
+        let mut physics_world = PhysicsWorld::new();
+        physics_world.set_timestep(1.0 / settings.physics_hz);
+
+        // Load tile map and get grid, objectives, patrol routes, and spawn points
+        let LoadedMap {
+            mut grid,
+            objectives,
+            patrol_routes,
+            spawn_points,
+        } = load_map(map_filename)?;
+
+        // Maps that declare no objectives are won by surviving
+        let objectives = if objectives.is_empty() {
+            vec![Objective::Survive {
+                seconds: settings.survive_seconds,
+            }]
+        } else {
+            objectives
+        };
+        let objectives = Objectives::new(objectives, &grid);
+
+        // Generate terrain
+        let mut terrain = Terrain::new(&mut physics_world, &grid);
+
+        // Maps too small for random spawns are reported like any other map problem
+        let spawn_error = |issue: MapIssue| MapError::Invalid {
+            filename: map_filename.to_string(),
+            issues: vec![issue],
+        };
+
+        // Generate player
+        generate_player(
+            &mut ecs_world,
+            &mut physics_world,
+            &grid,
+            &mut rng,
+            spawn_points.player,
+        )
+        .map_err(spawn_error)?;
+
+        // Generate trees
+        generate_trees(
+            &mut ecs_world,
+            &mut physics_world,
+            &grid,
+            &mut rng,
+            settings.num_of_trees,
+            &spawn_points.trees,
+        )
+        .map_err(spawn_error)?;
+
+        // Generate balls
+        generate_balls(
+            &mut ecs_world,
+            &mut physics_world,
+            &grid,
+            &mut rng,
+            settings.num_of_balls,
+            &spawn_points.balls,
+        )
+        .map_err(spawn_error)?;
+
+        // Generate witches
+        generate_witches(
+            &mut ecs_world,
+            &mut physics_world,
+            &grid,
+            &mut rng,
+            settings.num_of_witches,
+            &spawn_points.witches,
+        )
+        .map_err(spawn_error)?;
+
+        // Generate items for Collect objectives
+        generate_items(
+            &mut ecs_world,
+            &mut physics_world,
+            &grid,
+            &mut rng,
+            objectives.items_to_spawn(),
+            &spawn_points.items,
+        )
+        .map_err(spawn_error)?;
+
+        // Add trees to grid
+        for (_, (tree, body_handle)) in ecs_world.query::<(&Tree, &BodyHandle)>().iter() {
+            if let Some(body) = physics_world.bodies.get(body_handle.body_handle) {
+                let pos = body.translation();
+                let converted_pos = GridCoord::from_rapier3d_vec(*pos);
+                let radius = (tree.leaf_width / 2.0).floor() as isize;
+
+                // Trees cover the ground under their leaves, leaving blocks in place
+                grid.cover_area(converted_pos, radius, TileType::Tree);
+            }
+        }
+
+        // Keep terrain collider in step with the grid after trees are added
+        terrain.sync(&mut physics_world, &grid);
+
+        // Build navigation grid for witch pathfinding
+        let nav_grid = NavGrid::from_grid(&grid);
+
+        // Share patrol routes out between witches and send them to their first patrol point
+        for (index, (_, witch)) in ecs_world.query_mut::<&mut Witch>().into_iter().enumerate() {
+            if !patrol_routes.is_empty() {
+                witch.route = Some(index % patrol_routes.len());
+            }
+
+            witch.target = next_patrol_point(witch, &patrol_routes, &nav_grid, &mut rng);
+        }
+
+        Ok(Self {
+            ecs_world,
+            physics_world,
+            grid,
+            terrain,
+            nav_grid,
+            mouse_look,
+            stats,
+            map_time: 0.0,
+            objectives,
+            patrol_routes,
+            seed,
+            rng,
+            events: EventQueue::default(),
+            behaviour: WitchBehaviour::load_or_default(&settings.witch_config),
+            changed_tiles: Vec::new(),
+        })
+    }
+
+    // Step the simulation forward with this frame's actions
+    pub fn step(&mut self, actions: &ActionState) -> StepOutcome {
+        // Apply look input
+        let (look_x, look_y) = actions.look();
+        self.mouse_look.apply_look(look_x, look_y);
+
+        // Get player from ECS
+        if let Some((_, (_, body_handle))) = self
+            .ecs_world
+            .query::<(&mut Player, &BodyHandle)>()
+            .iter()
+            .next()
+        {
+            // Handle player movement
+            handle_player_movement(
+                &mut self.physics_world,
+                actions,
+                body_handle,
+                self.mouse_look.yaw(),
+            );
+        }
+
+        // Dig or place blocks where the player is looking
+        self.changed_tiles.clear();
+
+        if actions.is_pressed(Action::Dig) {
+            self.dig_block();
+        }
+        if actions.is_pressed(Action::PlaceGrass) {
+            self.place_block(TileType::Grass);
+        }
+        if actions.is_pressed(Action::PlaceStone) {
+            self.place_block(TileType::Stone);
+        }
+
+        // Get player position before stepping
+        let previous_position = self.player_position();
+
+        // Update physics world
+        self.physics_world.step();
+
+        // Collect this step's collision events for gameplay systems
+        self.events.clear();
+        collect_physics_events(&self.physics_world, &mut self.events);
+
+        // Update stats
+        let timestep = self.physics_world.timestep();
+        self.map_time += timestep;
+        self.stats.time_survived += timestep;
+
+        if let (Some(previous), Some(current)) = (previous_position, self.player_position()) {
+            let moved = vector![current.x - previous.x, 0.0, current.z - previous.z];
+            self.stats.distance_travelled += moved.norm();
+        }
+
+        // Update witch AI
+        self.stats.times_spotted += update_witch_ai(
+            &mut self.ecs_world,
+            &mut self.physics_world,
+            &self.nav_grid,
+            &self.behaviour,
+            &self.patrol_routes,
+            &mut self.rng,
+            timestep,
+        );
+
+        // Update objectives
+        let player_position = self.player_position();
+        update_objectives(
+            &mut self.ecs_world,
+            &mut self.physics_world,
+            &self.events,
+            &mut self.objectives,
+            player_position,
+            self.grid.tile_size,
+        );
+
+        // Game over if a witch touched the player
+        if witch_caught_player(&self.ecs_world, &self.physics_world) {
+            return StepOutcome::Lost(LossCause::CaughtByWitch);
+        }
+
+        // Win once every objective on the map is complete
+        if self.objectives.all_complete(self.map_time) {
+            self.stats.maps_completed += 1;
+            return StepOutcome::Won;
+        }
+
+        StepOutcome::Running
+    }
+
+    // Capture simulation state for a save game
+    // The RNG is stored as a seed drawn from a copy, so saving doesn't change this run
+    pub fn save_state(&self) -> SimulationState {
+        let rng_seed: u64 = self.rng.clone().random();
+
+        let bodies = &self.physics_world.bodies;
+        let body_state =
+            |body_handle: &BodyHandle| bodies.get(body_handle.body_handle).map(BodyState::capture);
+
+        let mut state = SimulationState {
+            seed: self.seed as i64,
+            rng_seed: rng_seed as i64,
+            stats: self.stats,
+            map_time: self.map_time,
+            objectives: self.objectives.clone(),
+            patrol_routes: self.patrol_routes.clone(),
+            yaw: self.mouse_look.yaw,
+            pitch: self.mouse_look.pitch,
+            grid: self.grid.clone(),
+            players: Vec::new(),
+            trees: Vec::new(),
+            balls: Vec::new(),
+            items: Vec::new(),
+            witches: Vec::new(),
+        };
+
+        for (_, (_, body_handle)) in self.ecs_world.query::<(&Player, &BodyHandle)>().iter() {
+            if let Some(body) = body_state(body_handle) {
+                state.players.push(SavedEntity {
+                    component: Player {},
+                    body,
+                });
+            }
+        }
+
+        for (_, (tree, body_handle)) in self.ecs_world.query::<(&Tree, &BodyHandle)>().iter() {
+            if let Some(body) = body_state(body_handle) {
+                state.trees.push(SavedEntity {
+                    component: tree.clone(),
+                    body,
+                });
+            }
+        }
+
+        for (_, (ball, body_handle)) in self
+            .ecs_world
+            .query::<(&crate::components::Ball, &BodyHandle)>()
+            .iter()
+        {
+            if let Some(body) = body_state(body_handle) {
+                state.balls.push(SavedEntity {
+                    component: ball.clone(),
+                    body,
+                });
+            }
+        }
+
+        for (_, (item, body_handle)) in self.ecs_world.query::<(&Item, &BodyHandle)>().iter() {
+            if let Some(body) = body_state(body_handle) {
+                state.items.push(SavedEntity {
+                    component: item.clone(),
+                    body,
+                });
+            }
+        }
+
+        for (_, (witch, body_handle)) in self.ecs_world.query::<(&Witch, &BodyHandle)>().iter() {
+            if let Some(body) = body_state(body_handle) {
+                state.witches.push(SavedEntity {
+                    component: SavedWitch {
+                        color: witch.color,
+                        state: witch.state,
+                        state_time: witch.state_time,
+                        target: witch.target.into(),
+                        route: witch.route,
+                        waypoint: witch.waypoint,
+                        home: witch.home.into(),
+                        facing: witch.facing.into(),
+                        last_known_position: witch.last_known_position.map(Into::into),
+                    },
+                    body,
+                });
+            }
+        }
+
+        state
+    }
+
+    // Restore a simulation from a save game
+    pub fn from_state(settings: &Settings, state: SimulationState) -> Self {
+        // Restore mouse look
+        let mut mouse_look = MouseLook::new(settings.mouse_sensitivity);
+        mouse_look.yaw = state.yaw;
+        mouse_look.pitch = state.pitch;
+
+        // Create ECS world
+        let mut ecs_world = World::new();
+
+        // Create physics world
+        let mut physics_world = PhysicsWorld::new();
+        physics_world.set_timestep(1.0 / settings.physics_hz);
+
+        let grid = state.grid;
+        let tile_size = grid.tile_size;
+
+        // Rebuild terrain from the grid
+        let terrain = Terrain::new(&mut physics_world, &grid);
+
+        // Restore player
+        for saved in state.players {
+            let (bundle, collider) =
+                create_player(&mut physics_world, tile_size, saved.body.translation());
+            restore_body(&mut physics_world, &bundle.1, &saved.body);
+            spawn_entity(&mut ecs_world, &mut physics_world, bundle, collider);
+        }
+
+        // Restore trees
+        for saved in state.trees {
+            let (bundle, collider) = create_tree(
+                &mut physics_world,
+                saved.body.translation(),
+                saved.component,
+            );
+            restore_body(&mut physics_world, &bundle.1, &saved.body);
+            spawn_entity(&mut ecs_world, &mut physics_world, bundle, collider);
+        }
+
+        // Restore balls
+        for saved in state.balls {
+            let (bundle, collider) = create_ball(
+                &mut physics_world,
+                saved.body.translation(),
+                saved.component,
+            );
+            restore_body(&mut physics_world, &bundle.1, &saved.body);
+            spawn_entity(&mut ecs_world, &mut physics_world, bundle, collider);
+        }
+
+        // Restore items
+        for saved in state.items {
+            let (bundle, collider) = create_item(
+                &mut physics_world,
+                saved.body.translation(),
+                saved.component,
+            );
+            spawn_entity(&mut ecs_world, &mut physics_world, bundle, collider);
+        }
+
+        // Restore witches
+        for saved in state.witches {
+            let (mut bundle, collider) = create_witch(
+                &mut physics_world,
+                tile_size,
+                saved.body.translation(),
+                Vector::from(saved.component.target),
+            );
+            bundle.0.color = saved.component.color;
+            bundle.0.state = saved.component.state;
+            bundle.0.state_time = saved.component.state_time;
+            bundle.0.route = saved.component.route;
+            bundle.0.waypoint = saved.component.waypoint;
+            bundle.0.home = Vector::from(saved.component.home);
+            bundle.0.facing = Vector::from(saved.component.facing);
+            bundle.0.last_known_position = saved.component.last_known_position.map(Vector::from);
+            restore_body(&mut physics_world, &bundle.1, &saved.body);
+            spawn_entity(&mut ecs_world, &mut physics_world, bundle, collider);
+        }
+
+        // Build navigation grid for witch pathfinding
+        let nav_grid = NavGrid::from_grid(&grid);
+
+        Self {
+            ecs_world,
+            physics_world,
+            grid,
+            terrain,
+            nav_grid,
+            mouse_look,
+            stats: state.stats,
+            map_time: state.map_time,
+            objectives: state.objectives,
+            patrol_routes: state.patrol_routes,
+            seed: state.seed as u64,
+            rng: StdRng::seed_from_u64(state.rng_seed as u64),
+            events: EventQueue::default(),
+            behaviour: WitchBehaviour::load_or_default(&settings.witch_config),
+            changed_tiles: Vec::new(),
+        }
+    }
+
+    // Place a tile, keeping the terrain collider and navigation grid in step
+    // Returns false if the tile is off the grid or already taken
+    pub fn place_tile(&mut self, coord: GridCoord, kind: TileType) -> bool {
+        if !self.grid.place(coord, kind) {
+            return false;
+        }
+
+        self.tile_changed(coord);
+
+        true
+    }
+
+    // Remove a tile, keeping the terrain collider and navigation grid in step
+    pub fn remove_tile(&mut self, coord: GridCoord) -> Option<TileType> {
+        let kind = self.grid.remove(coord)?;
+
+        self.tile_changed(coord);
+
+        Some(kind)
+    }
+
+    // Update everything built from the grid after a tile changes
+    fn tile_changed(&mut self, coord: GridCoord) {
+        self.terrain
+            .update_tile(&mut self.physics_world, &self.grid, coord);
+        self.nav_grid.update_column(&self.grid, coord.x, coord.z);
+        self.changed_tiles.push(coord);
+    }
+
+    // Dig out the block the player is looking at
+    fn dig_block(&mut self) {
+        let Some(target) = self.look_target() else {
+            return;
+        };
+
+        // Only grass and stone can be dug, the exit stays put
+        if self
+            .grid
+            .get(target.block)
+            .is_some_and(|tile| matches!(tile.kind, TileType::Grass | TileType::Stone))
+        {
+            self.remove_tile(target.block);
+        }
+    }
+
+    // Place a block against the face the player is looking at
+    fn place_block(&mut self, kind: TileType) {
+        let Some(coord) = self.look_target().and_then(|target| target.in_front) else {
+            return;
+        };
+
+        // Don't place blocks inside the player, witches, or anything else
+        if self
+            .terrain
+            .is_tile_clear(&self.physics_world, &self.grid, coord)
+        {
+            self.place_tile(coord, kind);
+        }
+    }
+
+    // Get the block the player is looking at, within reach
+    fn look_target(&self) -> Option<BlockTarget> {
+        let body_handle = {
+            let mut query = self.ecs_world.query::<(&Player, &BodyHandle)>();
+            let (_, (_, body_handle)) = query.iter().next()?;
+            body_handle.body_handle
+        };
+
+        let position = self.physics_world.bodies.get(body_handle)?.translation();
+        let forward = build_camera_forward(self.mouse_look.yaw(), self.mouse_look.pitch());
+        let ray = Ray::new(
+            point![position.x, position.y + EYE_HEIGHT, position.z],
+            forward,
+        );
+        let filter = QueryFilter::default().exclude_rigid_body(body_handle);
+
+        self.terrain
+            .target_block(&self.physics_world, &self.grid, &ray, filter)
+    }
+
+    // Get player position
+    pub fn player_position(&self) -> Option<Vector<f32>> {
+        let mut query = self.ecs_world.query::<(&Player, &BodyHandle)>();
+        let (_, (_, body_handle)) = query.iter().next()?;
+
+        self.physics_world
+            .bodies
+            .get(body_handle.body_handle)
+            .map(|body| *body.translation())
+    }
+}
+
+// Apply saved position and velocity to a restored body
+fn restore_body(physics_world: &mut PhysicsWorld, body_handle: &BodyHandle, state: &BodyState) {
+    if let Some(body) = physics_world.bodies.get_mut(body_handle.body_handle) {
+        state.apply(body);
+    }
+}
@@ -1,27 +1,59 @@
 use crate::components::*;
 use crate::physics::PhysicsWorld;
 use crate::settings::*;
+use crate::systems::behaviour::*;
+use crate::systems::events::*;
+use crate::systems::player::{SPRINT_SPEED, WALK_SPEED};
+use crate::world::navigation::*;
+use crate::world::patrol::*;
 use hecs::World;
-use rand::Rng;
-use rapier3d::prelude as rap3d;
+use rand::rngs::StdRng;
 use rapier3d::prelude::*;
-use raylib::prelude as rl;
-use raylib::prelude::*;
+use std::collections::VecDeque;
 
-// Update witch AI
-pub fn update_witch_ai(ecs_world: &mut World, physics_world: &mut PhysicsWorld) -> bool {
-    // Set 'game over' flag to false
-    let mut game_over = false;
+// Distance at which a witch counts as having reached a waypoint
+const WAYPOINT_RADIUS: f32 = 0.5;
+
+// How far a witch can see, and half the angle of her view cone
+const VIEW_RANGE: f32 = 15.0;
+const VIEW_HALF_ANGLE: f32 = std::f32::consts::FRAC_PI_3;
+
+// How far away walking and sprinting can be heard
+const FOOTSTEP_HEARING_RANGE: f32 = 4.0;
+const SPRINT_HEARING_RANGE: f32 = 10.0;
+
+// Speed below which the player counts as standing still
+const MOVING_SPEED: f32 = 0.5;
 
-    // Get player position
-    let player_position = {
+// Distance at which a witch counts as having reached her goal
+const GOAL_RADIUS: f32 = 1.0;
+
+// Update witch AI
+// Moves each witch through the states and transitions set out in her behaviour
+// Returns how many witches spotted the player this step
+pub fn update_witch_ai(
+    ecs_world: &mut World,
+    physics_world: &mut PhysicsWorld,
+    nav_grid: &NavGrid,
+    behaviour: &WitchBehaviour,
+    patrol_routes: &[PatrolRoute],
+    rng: &mut StdRng,
+    dt: f32,
+) -> u32 {
+    // Get player position and how far away their footsteps can be heard
+    let (player_position, noise_range) = {
         if let Some((_, (_, body_handle))) =
             ecs_world.query::<(&Player, &BodyHandle)>().iter().next()
         {
             if let Some(player_body) = physics_world.bodies.get(body_handle.body_handle) {
-                *player_body.translation()
+                (
+                    *player_body.translation(),
+                    noise_range(player_body.linvel()),
+                )
             } else {
-                eprintln!("Couldn't find Player while attempting call: update_witch_ai. Did player spawn?");
+                eprintln!(
+                    "Couldn't find Player while attempting call: update_witch_ai. Did player spawn?"
+                );
                 panic!();
             }
         } else {
@@ -32,6 +64,9 @@ pub fn update_witch_ai(ecs_world: &mut World, physics_world: &mut PhysicsWorld)
         }
     };
 
+    // Number of witches that spotted the player
+    let mut spotted = 0;
+
     for (_, (witch, body_handle)) in ecs_world.query::<(&mut Witch, &BodyHandle)>().iter() {
         // Get witch position
         let witch_position = match physics_world.bodies.get(body_handle.body_handle) {
@@ -39,135 +74,408 @@ pub fn update_witch_ai(ecs_world: &mut World, physics_world: &mut PhysicsWorld)
             None => continue,
         };
 
-        // Set 'should chase' flag to false
-        let mut should_chase = false;
-
-        // Get direction from witch to player
-        let direction_to_player = player_position - witch_position;
-
         // Get distance from witch to player
         // .norm() returns the length (magnitude) of the direction vector
-        let distance_to_player = direction_to_player.norm();
-
-        // Get direction without a length
-        let direction_to_player_normalized = direction_to_player / distance_to_player;
+        let distance_to_player = (player_position - witch_position).norm();
 
-        // Create a ray starting at witch postion and in normalized direction of player
-        let ray = rapier3d::parry::query::Ray::new(
-            point![witch_position.x, witch_position.y, witch_position.z],
-            direction_to_player_normalized,
+        // Check senses
+        let sees_player = can_see_player(
+            physics_world,
+            ecs_world,
+            witch,
+            witch_position,
+            player_position,
         );
+        let hears_player = !sees_player && distance_to_player <= noise_range;
 
-        // Filter out things we don't need to check
-        let filter = QueryFilter::default().exclude_collider(witch.collider_handle);
-
-        // Run query to see if ray collides with anything and return a hit_handle of that object
-        if let Some((hit_handle, _)) = physics_world.query_pipeline.cast_ray(
-            &physics_world.bodies,
-            &physics_world.colliders,
-            &ray,
-            distance_to_player,
-            true,
-            filter,
-        )
-        // If there's a collision was it the player?
-        {
-            if let Some(collider) = physics_world.colliders.get(hit_handle) {
-                let hit_entity = hecs::Entity::from_bits(collider.user_data as u64);
+        // Remember where the player was
+        if sees_player || hears_player {
+            witch.last_known_position = Some(player_position);
+        }
 
-                // If the hit entity was the player then witch should chase
-                if ecs_world.get::<&Player>(hit_entity.expect("")).is_ok() {
-                    should_chase = true;
-                }
+        // Count time in this state
+        witch.state_time += dt;
+
+        // Check which triggers are active
+        let config = behaviour.state(witch.state);
+        let reached_goal = goal_position(witch, player_position)
+            .is_none_or(|goal| horizontal_distance(goal, witch_position) <= GOAL_RADIUS);
+        let goal_unreachable = witch.goal_unreachable;
+
+        let next_state = behaviour.next_state(witch.state, |trigger| match trigger {
+            Trigger::SeesPlayer => sees_player,
+            Trigger::HearsPlayer => hears_player,
+            Trigger::LostPlayer => !sees_player,
+            Trigger::TimerExpired => config
+                .duration
+                .is_some_and(|duration| witch.state_time >= duration),
+            Trigger::ReachedGoal => reached_goal,
+            Trigger::GoalUnreachable => goal_unreachable,
+        });
+
+        // Switch state, rules back to the same state re-enter it to reset its timer and goal
+        if let Some(next_state) = next_state {
+            if debug_mode() {
+                println!("🧹 Witch: {:?} -> {:?}", witch.state, next_state);
             }
-        }
-        // If no collision then witch should chase player
-        else {
-            should_chase = true;
+
+            if next_state == WitchState::Chase && witch.state != WitchState::Chase {
+                spotted += 1;
+            }
+
+            enter_state(witch, next_state, patrol_routes, nav_grid, rng);
         }
 
-        // Switch state check
-        match (should_chase, &witch.state) {
-            // If 'should chase' flag is true and witch is currently patrolling
-            (true, WitchState::Patrolling) => {
-                if DEBUG_MODE {
-                    println!("👁️ Witch spotted the player. Switching to chase.");
-                }
+        // Get config for the (possibly new) state
+        let config = behaviour.state(witch.state);
 
-                witch.state = WitchState::Chasing;
-            }
-            // If 'should chase' flag is false and witch is currently chasing
-            (false, WitchState::Chasing) => {
-                if DEBUG_MODE {
-                    println!("🤫 Witch resumes patrol.");
-                }
+        // Get goal based on witch state
+        let goal_position = goal_position(witch, player_position);
+
+        // Plan path to goal
+        if let Some(goal_position) = goal_position {
+            plan_path(witch, nav_grid, witch_position, goal_position);
+        }
+
+        // Drop waypoints the witch has already reached
+        while let Some(waypoint) = witch.path.front() {
+            let waypoint_position = waypoint.to_rapier3d_vec(nav_grid.tile_size);
 
-                witch.state = WitchState::Patrolling;
-                witch.target = generate_patrol_point();
+            if horizontal_distance(waypoint_position, witch_position) < WAYPOINT_RADIUS {
+                witch.path.pop_front();
+            } else {
+                break;
             }
-            _ => {}
         }
 
         // Movement logic
         // Get witch body handle
         if let Some(witch_body) = physics_world.bodies.get_mut(body_handle.body_handle) {
-            // Get target based on witch state
-            let target = match witch.state {
-                WitchState::Chasing => player_position,
-                WitchState::Patrolling => raylib_vec_to_rapier_vec(witch.target),
-            };
-
-            // Get direction from witch to target
-            let direction_to_target = target - witch_position;
-
-            // Get distance from witch to target
-            // .norm() returns the length (magnitude) of the direction vector
-            let distance_to_target = direction_to_target.norm();
-
-            // If target is more than 1.0 away then head towards it
-            if distance_to_target > 1.0 {
-                // Get direction without a length
-                let direction_to_target_normalized = direction_to_target / distance_to_target;
-
-                // Set speed based on witch state
-                let speed = match witch.state {
-                    WitchState::Chasing => 3.0,
-                    WitchState::Patrolling => 5.0,
-                };
-
-                // Set witch in motion
-                witch_body.set_linvel(direction_to_target_normalized * speed, true);
-            }
-            // Otherwise (and if witch is in Patrolling state) pick a new point
-            else if matches!(witch.state, WitchState::Patrolling) {
-                if DEBUG_MODE {
-                    println!("🚶 Witch picked new patrol point: {:?}", witch.target);
+            let current_vel = *witch_body.linvel();
+
+            match goal_position {
+                // If goal is far enough away and state moves then head towards it
+                Some(goal_position)
+                    if config.speed > 0.0
+                        && horizontal_distance(goal_position, witch_position) > GOAL_RADIUS =>
+                {
+                    // Head for next waypoint or straight for the goal once the path runs out
+                    let target = match witch.path.front() {
+                        Some(waypoint) => waypoint.to_rapier3d_vec(nav_grid.tile_size),
+                        None => goal_position,
+                    };
+
+                    // Get direction from witch to target along the ground
+                    let direction_to_target = vector![
+                        target.x - witch_position.x,
+                        0.0,
+                        target.z - witch_position.z
+                    ];
+
+                    // Set witch in motion, leaving vertical velocity to gravity
+                    // and looking the way she's heading
+                    if direction_to_target.norm() > f32::EPSILON {
+                        witch.facing = direction_to_target.normalize();
+                        let movement = witch.facing * config.speed;
+                        witch_body.set_linvel(vector![movement.x, current_vel.y, movement.z], true);
+                    }
                 }
+                // Otherwise stand still and look around
+                _ => {
+                    witch_body.set_linvel(vector![0.0, current_vel.y, 0.0], true);
 
-                witch.target = generate_patrol_point();
+                    // Suspicious witches turn to face the noise
+                    if witch.state == WitchState::Suspicious
+                        && let Some(last_known_position) = witch.last_known_position
+                    {
+                        let direction = vector![
+                            last_known_position.x - witch_position.x,
+                            0.0,
+                            last_known_position.z - witch_position.z
+                        ];
+
+                        if direction.norm() > f32::EPSILON {
+                            witch.facing = direction.normalize();
+                        }
+                    } else if config.turn_speed > 0.0 {
+                        let angle = config.turn_speed * dt;
+                        let (sin, cos) = angle.sin_cos();
+
+                        witch.facing = vector![
+                            witch.facing.x * cos - witch.facing.z * sin,
+                            0.0,
+                            witch.facing.x * sin + witch.facing.z * cos
+                        ];
+                    }
+                }
             }
+        }
+    }
 
-            // If witch is in chasing state and it's reached the target, game over
-            if distance_to_target < 1.0 && matches!(witch.state, WitchState::Chasing) {
-                game_over = true;
+    spotted
+}
+
+// Move witch into a new state
+fn enter_state(
+    witch: &mut Witch,
+    state: WitchState,
+    patrol_routes: &[PatrolRoute],
+    nav_grid: &NavGrid,
+    rng: &mut StdRng,
+) {
+    witch.state = state;
+    witch.state_time = 0.0;
+    witch.goal_unreachable = false;
+
+    // Re-plan path for the new goal
+    witch.path.clear();
+    witch.path_goal = None;
+
+    match state {
+        // Start each patrol leg towards a new point
+        WitchState::Patrol => {
+            witch.target = next_patrol_point(witch, patrol_routes, nav_grid, rng);
+
+            if debug_mode() {
+                println!("🚶 Witch picked new patrol point: {:?}", witch.target);
             }
         }
+        // Forget the player once heading home
+        WitchState::Return => witch.last_known_position = None,
+        _ => {}
     }
+}
 
-    game_over
+// Where a witch's state is taking her, or None if she stays where she is
+fn goal_position(witch: &Witch, player_position: Vector<f32>) -> Option<Vector<f32>> {
+    match witch.state {
+        WitchState::Chase => Some(player_position),
+        WitchState::Investigate => witch.last_known_position,
+        WitchState::Patrol => Some(witch.target),
+        WitchState::Return => Some(witch.home),
+        WitchState::Idle | WitchState::Suspicious | WitchState::Search => None,
+    }
 }
 
-// Generate a random point for witch to patrol to
-pub fn generate_patrol_point() -> Vector3 {
-    let mut rng = rand::rng();
-    Vector3::new(
-        rng.random_range(-10.0..=10.0),
-        0.0,
-        rng.random_range(-10.0..=10.0),
-    )
+// Plan path to goal if it has moved to a different cell
+fn plan_path(
+    witch: &mut Witch,
+    nav_grid: &NavGrid,
+    witch_position: Vector<f32>,
+    goal_position: Vector<f32>,
+) {
+    // Get goal cell on the navigation grid
+    let goal_cell = nav_grid.cell_from_rapier3d_vec(goal_position);
+
+    if witch.path_goal == Some(goal_cell) {
+        return;
+    }
+
+    let start_cell = nav_grid.cell_from_rapier3d_vec(witch_position);
+
+    match nav_grid.find_path(start_cell, goal_cell) {
+        Some(path) => {
+            if debug_mode() {
+                println!("🧭 Witch planned path of {} waypoints.", path.len());
+            }
+
+            witch.path = VecDeque::from(path);
+            witch.goal_unreachable = false;
+        }
+        None => {
+            if debug_mode() {
+                println!("🚧 Witch can't reach goal: {goal_position:?}");
+            }
+
+            witch.path.clear();
+            witch.goal_unreachable = true;
+        }
+    }
+
+    witch.path_goal = Some(goal_cell);
 }
 
-// Convert a Raylib Vector3 to a Rapier Vector<f32>
-pub fn raylib_vec_to_rapier_vec(v: rl::Vector3) -> rap3d::Vector<f32> {
-    rap3d::vector![v.x, v.y, v.z]
+// Check if a witch can see the player
+// The player has to be in range, inside the witch's view cone, and not hidden behind anything
+fn can_see_player(
+    physics_world: &PhysicsWorld,
+    ecs_world: &World,
+    witch: &Witch,
+    witch_position: Vector<f32>,
+    player_position: Vector<f32>,
+) -> bool {
+    // Get direction from witch to player
+    let direction_to_player = player_position - witch_position;
+
+    // Get distance from witch to player
+    let distance_to_player = direction_to_player.norm();
+
+    if distance_to_player > VIEW_RANGE {
+        return false;
+    }
+
+    // Check player is inside the view cone, looking along the ground
+    let flat_direction = vector![direction_to_player.x, 0.0, direction_to_player.z];
+
+    if flat_direction.norm() > f32::EPSILON
+        && witch.facing.dot(&flat_direction.normalize()) < VIEW_HALF_ANGLE.cos()
+    {
+        return false;
+    }
+
+    // Get direction without a length
+    let direction_to_player_normalized = direction_to_player / distance_to_player;
+
+    // Create a ray starting at witch postion and in normalized direction of player
+    let ray = rapier3d::parry::query::Ray::new(
+        point![witch_position.x, witch_position.y, witch_position.z],
+        direction_to_player_normalized,
+    );
+
+    // Filter out things we don't need to check
+    let filter = QueryFilter::default().exclude_collider(witch.collider_handle);
+
+    // Run query to see if ray collides with anything and return a hit_handle of that object
+    match physics_world.query_pipeline.cast_ray(
+        &physics_world.bodies,
+        &physics_world.colliders,
+        &ray,
+        distance_to_player,
+        true,
+        filter,
+    ) {
+        // If there's a collision was it the player?
+        Some((hit_handle, _)) => entity_from_collider(physics_world, hit_handle)
+            .is_some_and(|hit_entity| ecs_world.get::<&Player>(hit_entity).is_ok()),
+        // If no collision then nothing is in the way
+        None => true,
+    }
+}
+
+// How far away the player's footsteps can be heard
+fn noise_range(player_velocity: &Vector<f32>) -> f32 {
+    let speed = vector![player_velocity.x, 0.0, player_velocity.z].norm();
+
+    if speed > (WALK_SPEED + SPRINT_SPEED) / 2.0 {
+        SPRINT_HEARING_RANGE
+    } else if speed > MOVING_SPEED {
+        FOOTSTEP_HEARING_RANGE
+    } else {
+        0.0
+    }
+}
+
+// Check if a chasing witch is touching the player
+// Reads live contacts from the narrow phase instead of the event queue: the queue only holds
+// the last step's start and stop events, but being caught depends on touching while chasing,
+// and a witch can switch to Chase while already against the player. Following this through the
+// queue would mean keeping a copy of every touching pair, which the narrow phase already is
+pub fn witch_caught_player(ecs_world: &World, physics_world: &PhysicsWorld) -> bool {
+    let mut player_query = ecs_world.query::<(&Player, &BodyHandle)>();
+    let Some((_, (_, player_body))) = player_query.iter().next() else {
+        return false;
+    };
+    let Some(player_body) = physics_world.bodies.get(player_body.body_handle) else {
+        return false;
+    };
+
+    ecs_world.query::<&Witch>().iter().any(|(_, witch)| {
+        witch.state == WitchState::Chase
+            && player_body.colliders().iter().any(|player_collider| {
+                physics_world.colliders_touching(*player_collider, witch.collider_handle)
+            })
+    })
+}
+
+// Get the next point for a witch to patrol to
+// Follows her patrol route if she has one, otherwise picks a random walkable tile
+pub fn next_patrol_point(
+    witch: &mut Witch,
+    patrol_routes: &[PatrolRoute],
+    nav_grid: &NavGrid,
+    rng: &mut StdRng,
+) -> Vector<f32> {
+    if let Some(route) = witch.route.and_then(|index| patrol_routes.get(index))
+        && let Some(point) = route.point(witch.waypoint)
+    {
+        witch.waypoint = (witch.waypoint + 1) % route.cycle_len();
+
+        return point.to_rapier3d_vec(nav_grid.tile_size);
+    }
+
+    nav_grid
+        .random_walkable_cell(rng)
+        .map(|cell| cell.to_rapier3d_vec(nav_grid.tile_size))
+        .unwrap_or(witch.home)
+}
+
+// Distance between two points ignoring height
+fn horizontal_distance(a: Vector<f32>, b: Vector<f32>) -> f32 {
+    ((a.x - b.x).powi(2) + (a.z - b.z).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::spawn::*;
+    use crate::world::grid::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn chasing_witch_catches_player_she_was_already_touching() {
+        let mut ecs_world = World::new();
+        let mut physics_world = PhysicsWorld::new();
+
+        // Bodies overlap from the start, so their contact begins before any chase
+        let (player, _) = create_player(&mut physics_world, 1.0, vector![0.0, 0.0, 0.0]);
+        ecs_world.spawn(player);
+        let (witch, _) = create_witch(
+            &mut physics_world,
+            1.0,
+            vector![1.1, 0.0, 0.0],
+            vector![0.0, 0.0, 0.0],
+        );
+        let witch = ecs_world.spawn(witch);
+
+        physics_world.step();
+        assert!(!witch_caught_player(&ecs_world, &physics_world));
+
+        // Switching to Chase mid-contact starts no new collision
+        ecs_world.get::<&mut Witch>(witch).unwrap().state = WitchState::Chase;
+        physics_world.step();
+        assert!(witch_caught_player(&ecs_world, &physics_world));
+    }
+
+    #[test]
+    fn re_entering_patrol_heads_for_the_next_waypoint() {
+        let mut physics_world = PhysicsWorld::new();
+        let mut grid = Grid::new(3, 1, 1);
+        for x in 0..3 {
+            grid.set(Tile {
+                kind: TileType::Grass,
+                coord: GridCoord { x, y: 0, z: 0 },
+            });
+        }
+        let nav_grid = NavGrid::from_grid(&grid);
+        let routes = [PatrolRoute {
+            points: (0..3).map(|x| GridCoord { x, y: 0, z: 0 }).collect(),
+            looped: true,
+        }];
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let ((mut witch, _), _) = create_witch(
+            &mut physics_world,
+            1.0,
+            vector![0.0, 0.0, 0.0],
+            vector![0.0, 0.0, 0.0],
+        );
+        witch.route = Some(0);
+
+        enter_state(&mut witch, WitchState::Patrol, &routes, &nav_grid, &mut rng);
+        witch.state_time = 5.0;
+        enter_state(&mut witch, WitchState::Patrol, &routes, &nav_grid, &mut rng);
+
+        assert_eq!(witch.state_time, 0.0);
+        assert_eq!(witch.target, vector![1.0, 0.0, 0.0]);
+    }
 }
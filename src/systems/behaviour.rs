@@ -0,0 +1,216 @@
+use crate::components::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+// Witch behaviour config shipped with the game, used as the defaults
+const DEFAULT_CONFIG: &str = include_str!("../../assets/config/witches.toml");
+
+// Something that can move a witch from one state to another
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub enum Trigger {
+    // Player is in view
+    SeesPlayer,
+    // Player can be heard but not seen
+    HearsPlayer,
+    // Player is not in view
+    LostPlayer,
+    // Witch has been in her state for its duration
+    TimerExpired,
+    // Witch has arrived where her state is taking her
+    ReachedGoal,
+    // No path to where her state is taking her
+    GoalUnreachable,
+}
+
+// State config
+// How a witch behaves while in a state
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StateConfig {
+    // Movement speed towards the state's goal (0.0 stands still)
+    pub speed: f32,
+    // How fast to turn and look around while standing (radians per second)
+    pub turn_speed: f32,
+    // Seconds before TimerExpired fires, never if not set
+    pub duration: Option<f32>,
+}
+
+// Transition rule
+// When a witch in one of the `from` states sees the trigger, she moves to the `to` state
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransitionRule {
+    pub from: Vec<WitchState>,
+    pub on: Trigger,
+    pub to: WitchState,
+}
+
+// Witch behaviour
+// Per-state settings and the rules for moving between states, checked in order
+#[derive(Clone, Debug)]
+pub struct WitchBehaviour {
+    pub states: HashMap<WitchState, StateConfig>,
+    pub transitions: Vec<TransitionRule>,
+    // Config actually in use, the defaults with the file laid over them, so replays can check it
+    pub config: String,
+}
+
+// Witch behaviour config file layout
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BehaviourConfig {
+    #[serde(default)]
+    states: HashMap<WitchState, StateConfig>,
+    transitions: Option<Vec<TransitionRule>>,
+}
+
+// Functions for WitchBehaviour
+impl WitchBehaviour {
+    // Load witch behaviour from config file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BehaviourConfigError> {
+        let text = std::fs::read_to_string(path).map_err(BehaviourConfigError::Io)?;
+
+        Self::from_config(&text)
+    }
+
+    // Read witch behaviour from config text laid over the defaults
+    // State settings missing from the text keep their defaults, transitions replace the defaults if given
+    pub fn from_config(text: &str) -> Result<Self, BehaviourConfigError> {
+        let mut merged: toml::Table = DEFAULT_CONFIG
+            .parse()
+            .expect("shipped witch behaviour config is invalid");
+        let overrides: toml::Table = text.parse().map_err(BehaviourConfigError::Parse)?;
+
+        merge_tables(&mut merged, overrides);
+
+        let text = merged.to_string();
+        let config: BehaviourConfig = merged.try_into().map_err(BehaviourConfigError::Parse)?;
+
+        Ok(Self {
+            states: config.states,
+            transitions: config.transitions.unwrap_or_default(),
+            config: text,
+        })
+    }
+
+    // Load witch behaviour from config file or fall back to defaults
+    pub fn load_or_default(path: impl AsRef<Path>) -> Self {
+        match Self::load(&path) {
+            Ok(behaviour) => behaviour,
+            Err(e) => {
+                eprintln!(
+                    "Failed to load witch behaviour {}: {e}. Using default behaviour.",
+                    path.as_ref().display()
+                );
+                Self::default()
+            }
+        }
+    }
+
+    // Get config for a state
+    pub fn state(&self, state: WitchState) -> StateConfig {
+        self.states.get(&state).copied().unwrap_or_default()
+    }
+
+    // Get the state to move to from the first matching rule, if any
+    pub fn next_state(
+        &self,
+        state: WitchState,
+        is_active: impl Fn(Trigger) -> bool,
+    ) -> Option<WitchState> {
+        self.transitions
+            .iter()
+            .find(|rule| rule.from.contains(&state) && is_active(rule.on))
+            .map(|rule| rule.to)
+    }
+}
+
+// Default witch behaviour, read from the config shipped in assets so there's one copy of it
+impl Default for WitchBehaviour {
+    fn default() -> Self {
+        Self::from_config("").expect("shipped witch behaviour config is invalid")
+    }
+}
+
+// Lay one TOML table over another, merging tables in both key by key
+// Anything else, such as the transitions list, is replaced whole
+fn merge_tables(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => {
+                merge_tables(base, overrides)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+// Witch behaviour config error
+#[derive(Debug)]
+pub enum BehaviourConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+}
+
+// Display witch behaviour config error
+impl fmt::Display for BehaviourConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BehaviourConfigError::Io(e) => write!(f, "{e}"),
+            BehaviourConfigError::Parse(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_behaviour_covers_every_state() {
+        let behaviour = WitchBehaviour::default();
+
+        assert_eq!(behaviour.states.len(), 7);
+        assert!(!behaviour.transitions.is_empty());
+        assert_eq!(
+            behaviour.next_state(WitchState::Patrol, |trigger| trigger == Trigger::SeesPlayer),
+            Some(WitchState::Chase)
+        );
+    }
+
+    #[test]
+    fn partial_state_config_keeps_other_defaults() {
+        let defaults = WitchBehaviour::default();
+        let behaviour = WitchBehaviour::from_config("[states.Idle]\nspeed = 1.0\n").unwrap();
+
+        let idle = behaviour.state(WitchState::Idle);
+        assert_eq!(idle.speed, 1.0);
+        assert_eq!(idle.turn_speed, defaults.state(WitchState::Idle).turn_speed);
+        assert_eq!(idle.duration, defaults.state(WitchState::Idle).duration);
+        assert_eq!(behaviour.transitions, defaults.transitions);
+    }
+
+    #[test]
+    fn config_in_use_includes_defaults_and_overrides() {
+        let defaults = WitchBehaviour::default();
+        let behaviour = WitchBehaviour::from_config("[states.Idle]\nspeed = 1.0\n").unwrap();
+
+        assert_eq!(
+            WitchBehaviour::from_config("").unwrap().config,
+            defaults.config
+        );
+        assert_ne!(behaviour.config, defaults.config);
+        assert!(behaviour.config.contains("[states.Chase]"));
+    }
+
+    #[test]
+    fn unknown_state_settings_are_rejected() {
+        let result = WitchBehaviour::from_config("[states.Idle]\nsped = 1.0\n");
+
+        assert!(matches!(result, Err(BehaviourConfigError::Parse(_))));
+    }
+}
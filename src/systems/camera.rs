@@ -0,0 +1,21 @@
+use crate::components::*;
+use crate::physics::*;
+use crate::systems::player::*;
+use raylib::prelude::*;
+
+// Update camera
+pub fn update_camera(
+    camera: &mut Camera3D,
+    physics_world: &PhysicsWorld,
+    body_handle: &BodyHandle,
+    yaw: f32,
+    pitch: f32,
+    alpha: f32,
+) {
+    if let Some(position) = physics_world.interpolated_translation(body_handle.body_handle, alpha) {
+        camera.position = Vector3::new(position.x, position.y + EYE_HEIGHT, position.z);
+
+        let forward = build_camera_forward(yaw, pitch);
+        camera.target = camera.position + Vector3::new(forward.x, forward.y, forward.z);
+    }
+}
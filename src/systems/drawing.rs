@@ -1,29 +1,15 @@
 use crate::components::*;
+use crate::input::actions::*;
+use crate::input::bindings::*;
 use crate::physics::*;
 use hecs::World;
 use rapier3d::prelude::*;
 use raylib::prelude::*;
 
-// Draw blocks
-pub fn draw_blocks(
-    d3d: &mut RaylibMode3D<RaylibDrawHandle>,
-    ecs_world: &World,
-    physics_world: &PhysicsWorld,
-) {
-    for (_, (block, body_handle)) in ecs_world.query::<(&Block, &BodyHandle)>().iter() {
-        if let Some(body) = physics_world.bodies.get(body_handle.body_handle) {
-            // Get position from physics world
-            let position = body.translation();
-
-            // Draw block
-            d3d.draw_cube(
-                Vector3::new(position.x, position.y, position.z),
-                block.width,
-                block.height,
-                block.width,
-                block.color,
-            );
-        }
+// Convert component color to Raylib color
+impl From<Rgba> for Color {
+    fn from(color: Rgba) -> Self {
+        Color::new(color.r, color.g, color.b, color.a)
     }
 }
 
@@ -38,26 +24,29 @@ pub fn draw_forest(
         if let Some(body) = physics_world.bodies.get(body_handle.body_handle) {
             let position = body.translation();
 
+            // Body is centred on the whole tree, so find the bottom of the trunk
+            let base = position.y - (tree.trunk_height + tree.leaf_height) / 2.0;
+
             // Draw leaves
             d3d.draw_cube(
                 Vector3::new(
                     position.x,
-                    tree.trunk_height + tree.leaf_height / 2.0,
+                    base + tree.trunk_height + tree.leaf_height / 2.0,
                     position.z,
                 ),
                 tree.leaf_width,
                 tree.leaf_height,
                 tree.leaf_width,
-                tree.leaf_color,
+                Color::from(tree.leaf_color),
             );
 
             // Draw trunk
             d3d.draw_cube(
-                Vector3::new(position.x, tree.trunk_height / 2.0, position.z),
+                Vector3::new(position.x, base + tree.trunk_height / 2.0, position.z),
                 0.25,
                 tree.trunk_height,
                 0.25,
-                tree.trunk_color,
+                Color::from(tree.trunk_color),
             );
         }
     }
@@ -68,20 +57,42 @@ pub fn draw_balls(
     d3d: &mut RaylibMode3D<RaylibDrawHandle>,
     ecs_world: &World,
     physics_world: &PhysicsWorld,
+    alpha: f32,
 ) {
     for (_, (ball, body_handle)) in ecs_world
         .query::<(&crate::components::Ball, &BodyHandle)>()
         .iter()
     {
-        // Get position from physics world
+        // Get position from physics world, between the last two steps
+        if let Some(position) =
+            physics_world.interpolated_translation(body_handle.body_handle, alpha)
+        {
+            // Draw ball
+            d3d.draw_sphere(
+                Vector3::new(position.x, position.y, position.z),
+                ball.size,
+                Color::from(ball.color),
+            );
+        }
+    }
+}
+
+// Draw items
+pub fn draw_items(
+    d3d: &mut RaylibMode3D<RaylibDrawHandle>,
+    ecs_world: &World,
+    physics_world: &PhysicsWorld,
+) {
+    for (_, (item, body_handle)) in ecs_world.query::<(&Item, &BodyHandle)>().iter() {
         if let Some(body) = physics_world.bodies.get(body_handle.body_handle) {
+            // Get position from physics world
             let position = body.translation();
 
-            // Draw ball
+            // Draw item
             d3d.draw_sphere(
                 Vector3::new(position.x, position.y, position.z),
-                ball.size,
-                ball.color,
+                item.size,
+                Color::from(item.color),
             );
         }
     }
@@ -92,26 +103,55 @@ pub fn draw_witches(
     d3d: &mut RaylibMode3D<RaylibDrawHandle>,
     ecs_world: &World,
     physics_world: &PhysicsWorld,
+    alpha: f32,
 ) {
     for (_, (witch, body_handle)) in ecs_world.query::<(&Witch, &BodyHandle)>().iter() {
-        // Get position from physics world
-        if let Some(body) = physics_world.bodies.get(body_handle.body_handle) {
-            let position = body.translation();
-
+        // Get position from physics world, between the last two steps
+        if let Some(position) =
+            physics_world.interpolated_translation(body_handle.body_handle, alpha)
+        {
             // Draw witch
             d3d.draw_cube(
                 Vector3::new(position.x, position.y, position.z),
                 witch.width,
                 witch.height,
                 witch.width,
-                witch.color,
+                Color::from(witch.color),
             );
+
+            // Draw alert marker above witches that are after the player
+            let marker_color = match witch.state {
+                WitchState::Chase => Some(Rgba::RED),
+                WitchState::Suspicious | WitchState::Investigate | WitchState::Search => {
+                    Some(Rgba::ORANGE)
+                }
+                WitchState::Idle | WitchState::Patrol | WitchState::Return => None,
+            };
+
+            if let Some(color) = marker_color {
+                d3d.draw_sphere(
+                    Vector3::new(
+                        position.x,
+                        position.y + witch.height / 2.0 + 0.5,
+                        position.z,
+                    ),
+                    0.2,
+                    Color::from(color),
+                );
+            }
         }
     }
 }
 
 //Draw HUD
-pub fn draw_hud(d: &mut RaylibDrawHandle) {
+pub fn draw_hud(d: &mut RaylibDrawHandle, input_map: &InputMap) {
+    let movement = input_map.key_names(&[
+        Action::MoveForward,
+        Action::StrafeLeft,
+        Action::MoveBackward,
+        Action::StrafeRight,
+    ]);
+
     d.draw_rectangle(10, 10, 220, 70, Color::GRAY);
     d.draw_rectangle_lines(10, 10, 220, 70, Color::BLUE);
     d.draw_text(
@@ -121,10 +161,49 @@ pub fn draw_hud(d: &mut RaylibDrawHandle) {
         10,
         Color::BLACK,
     );
-    d.draw_text("- Move with keys: W, A, S, D", 40, 40, 10, Color::DARKGRAY);
+    d.draw_text(
+        &format!("- Move with keys: {movement}"),
+        40,
+        40,
+        10,
+        Color::DARKGRAY,
+    );
     d.draw_text("- Mouse move to look around", 40, 60, 10, Color::DARKGRAY);
 }
 
+// Draw run info
+pub fn draw_run_info(d: &mut RaylibDrawHandle, map_label: &str, seed: u64) {
+    d.draw_rectangle(10, 90, 220, 50, Color::GRAY);
+    d.draw_rectangle_lines(10, 90, 220, 50, Color::BLUE);
+    d.draw_text(&format!("Map: {map_label}"), 20, 100, 10, Color::BLACK);
+    d.draw_text(&format!("Seed: {seed}"), 20, 120, 10, Color::DARKGRAY);
+}
+
+// Draw objectives, with completed ones ticked off
+pub fn draw_objectives(d: &mut RaylibDrawHandle, objectives: &[(String, bool)]) {
+    let height = 30 + objectives.len() as i32 * 20;
+
+    d.draw_rectangle(10, 150, 220, height, Color::GRAY);
+    d.draw_rectangle_lines(10, 150, 220, height, Color::BLUE);
+    d.draw_text("Objectives:", 20, 160, 10, Color::BLACK);
+
+    for (i, (text, complete)) in objectives.iter().enumerate() {
+        let (mark, color) = if *complete {
+            ("[x]", Color::DARKGREEN)
+        } else {
+            ("[ ]", Color::DARKGRAY)
+        };
+
+        d.draw_text(
+            &format!("{mark} {text}"),
+            40,
+            180 + i as i32 * 20,
+            10,
+            color,
+        );
+    }
+}
+
 // Draw collider wireframes
 pub fn debug_colliders(
     d3d: &mut RaylibMode3D<RaylibDrawHandle>,
@@ -158,6 +237,19 @@ pub fn debug_colliders(
                 half_extents.z * 2.0,
                 color,
             );
+        } else if let Some(voxels) = shape.as_voxels() {
+            // Draw each filled voxel of the terrain
+            let size = voxels.voxel_size();
+            for voxel in voxels.voxels().filter(|voxel| !voxel.state.is_empty()) {
+                let center = iso * voxel.center;
+                d3d.draw_cube_wires(
+                    Vector3::new(center.x, center.y, center.z),
+                    size.x,
+                    size.y,
+                    size.z,
+                    color,
+                );
+            }
         } else {
             // Add support for other shapes if needed
             d3d.draw_text("Unsupported shape", 10, 10, 20, color);
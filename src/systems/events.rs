@@ -0,0 +1,106 @@
+use crate::physics::*;
+use hecs::Entity;
+use rapier3d::prelude::*;
+use std::collections::VecDeque;
+
+// Gameplay event
+// Physics events mapped back to the ECS entities involved
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    // Two entities started touching
+    CollisionStarted { a: Entity, b: Entity },
+    // Two entities stopped touching
+    CollisionStopped { a: Entity, b: Entity },
+    // Two entities pushed on each other harder than a collider's force threshold
+    ContactForce { a: Entity, b: Entity, force: f32 },
+}
+
+// Functions for GameEvent
+impl GameEvent {
+    // Entities involved
+    pub fn entities(&self) -> (Entity, Entity) {
+        match *self {
+            GameEvent::CollisionStarted { a, b }
+            | GameEvent::CollisionStopped { a, b }
+            | GameEvent::ContactForce { a, b, .. } => (a, b),
+        }
+    }
+
+    // If this event involves an entity, get the other one
+    pub fn other(&self, entity: Entity) -> Option<Entity> {
+        match self.entities() {
+            (a, b) if a == entity => Some(b),
+            (a, b) if b == entity => Some(a),
+            _ => None,
+        }
+    }
+}
+
+// Event queue
+// Filled after each physics step and read by gameplay systems until the next step
+#[derive(Debug, Default)]
+pub struct EventQueue {
+    events: VecDeque<GameEvent>,
+}
+
+// Functions for EventQueue
+impl EventQueue {
+    // Add event
+    pub fn push(&mut self, event: GameEvent) {
+        self.events.push_back(event);
+    }
+
+    // Events from the last step
+    pub fn iter(&self) -> impl Iterator<Item = &GameEvent> {
+        self.events.iter()
+    }
+
+    // Remove all events
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+// Collect physics events into the queue
+// Events for colliders that no longer exist or have no entity are dropped
+pub fn collect_physics_events(physics_world: &PhysicsWorld, queue: &mut EventQueue) {
+    for event in physics_world.drain_collision_events() {
+        let (Some(a), Some(b)) = (
+            entity_from_collider(physics_world, event.collider1()),
+            entity_from_collider(physics_world, event.collider2()),
+        ) else {
+            continue;
+        };
+
+        if event.started() {
+            queue.push(GameEvent::CollisionStarted { a, b });
+        } else {
+            queue.push(GameEvent::CollisionStopped { a, b });
+        }
+    }
+
+    for event in physics_world.drain_contact_force_events() {
+        let (Some(a), Some(b)) = (
+            entity_from_collider(physics_world, event.collider1),
+            entity_from_collider(physics_world, event.collider2),
+        ) else {
+            continue;
+        };
+
+        queue.push(GameEvent::ContactForce {
+            a,
+            b,
+            force: event.total_force_magnitude,
+        });
+    }
+}
+
+// Get ECS entity from collider's user_data field
+pub fn entity_from_collider(
+    physics_world: &PhysicsWorld,
+    collider_handle: ColliderHandle,
+) -> Option<Entity> {
+    let collider = physics_world.colliders.get(collider_handle)?;
+
+    Entity::from_bits(collider.user_data as u64)
+}
@@ -1,5 +1,10 @@
 pub mod ai;
+pub mod behaviour;
+pub mod camera;
 pub mod drawing;
+pub mod events;
+pub mod objectives;
 pub mod player;
 pub mod spawn;
 pub mod terrain;
+pub mod terrain_mesh;
@@ -0,0 +1,142 @@
+use crate::components::*;
+use crate::physics::*;
+use crate::systems::events::*;
+use crate::world::grid::*;
+use hecs::{Entity, World};
+use rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Objective
+// Declared per map in Tiled map properties; all of a map's objectives clear it
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Objective {
+    // Survive for a number of seconds ("SurviveSeconds" property)
+    Survive { seconds: f32 },
+    // Collect items scattered around the map ("CollectItems" property)
+    Collect { count: u32 },
+    // Reach an Exit tile ("ReachExit" property)
+    ReachExit,
+}
+
+// Objectives
+// A map's objectives and the progress made on them
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Objectives {
+    pub objectives: Vec<Objective>,
+    pub collected: u32,
+    pub exit_reached: bool,
+    pub exits: Vec<GridCoord>,
+}
+
+// Functions for Objectives
+impl Objectives {
+    // Track objectives on a map, finding its exit tiles
+    pub fn new(objectives: Vec<Objective>, grid: &Grid) -> Self {
+        let exits = grid
+            .tiles()
+            .filter(|tile| matches!(tile.kind, TileType::Exit))
+            .map(|tile| tile.coord)
+            .collect();
+
+        Self {
+            objectives,
+            collected: 0,
+            exit_reached: false,
+            exits,
+        }
+    }
+
+    // Number of items to scatter for Collect objectives
+    pub fn items_to_spawn(&self) -> u32 {
+        self.objectives
+            .iter()
+            .map(|objective| match objective {
+                Objective::Collect { count } => *count,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    // Is an objective complete
+    pub fn is_complete(&self, objective: &Objective, map_time: f32) -> bool {
+        match objective {
+            Objective::Survive { seconds } => map_time >= *seconds,
+            Objective::Collect { count } => self.collected >= *count,
+            Objective::ReachExit => self.exit_reached,
+        }
+    }
+
+    // Is every objective complete
+    pub fn all_complete(&self, map_time: f32) -> bool {
+        self.objectives
+            .iter()
+            .all(|objective| self.is_complete(objective, map_time))
+    }
+
+    // Objective descriptions for the HUD
+    pub fn describe(&self, map_time: f32) -> Vec<(String, bool)> {
+        self.objectives
+            .iter()
+            .map(|objective| {
+                let text = match objective {
+                    Objective::Survive { seconds } => {
+                        format!("Survive: {:.0}s", (seconds - map_time).max(0.0).ceil())
+                    }
+                    Objective::Collect { count } => {
+                        format!("Collect items: {}/{count}", self.collected.min(*count))
+                    }
+                    Objective::ReachExit => "Reach the exit".to_string(),
+                };
+
+                (text, self.is_complete(objective, map_time))
+            })
+            .collect()
+    }
+}
+
+// Update objectives
+// Picks up items the player touched this step and checks if the player is on an exit
+pub fn update_objectives(
+    ecs_world: &mut World,
+    physics_world: &mut PhysicsWorld,
+    events: &EventQueue,
+    objectives: &mut Objectives,
+    player_position: Option<Vector<f32>>,
+    tile_size: f32,
+) {
+    let Some((player, _)) = ecs_world.query::<&Player>().iter().next() else {
+        return;
+    };
+
+    // Get items touched by the player
+    let touched: Vec<Entity> = events
+        .iter()
+        .filter(|event| matches!(event, GameEvent::CollisionStarted { .. }))
+        .filter_map(|event| event.other(player))
+        .filter(|other| ecs_world.get::<&Item>(*other).is_ok())
+        .collect();
+
+    // Pick up items, removing them from both worlds
+    for item in touched {
+        if let Ok((_, body_handle)) = ecs_world.remove::<(Item, BodyHandle)>(item) {
+            physics_world.remove_body(body_handle.body_handle);
+            let _ = ecs_world.despawn(item);
+
+            objectives.collected += 1;
+        }
+    }
+
+    // Check if player is standing over an exit tile
+    if let Some(position) = player_position
+        && !objectives.exit_reached
+    {
+        let half_tile = tile_size / 2.0;
+
+        objectives.exit_reached = objectives.exits.iter().any(|exit| {
+            let exit_position = exit.to_rapier3d_vec(tile_size);
+
+            (position.x - exit_position.x).abs() <= half_tile
+                && (position.z - exit_position.z).abs() <= half_tile
+        });
+    }
+}
@@ -1,14 +1,27 @@
 use crate::components::*;
+use crate::input::actions::*;
 use crate::physics::*;
 use rapier3d::na::Vector3 as RapierVector3;
 use rapier3d::prelude::*;
-use raylib::consts::KeyboardKey::*;
-use raylib::prelude::*;
+
+// Movement speeds when walking and sprinting
+pub const WALK_SPEED: f32 = 4.0;
+pub const SPRINT_SPEED: f32 = 7.0;
+
+// Height of the player's eyes above their centre
+pub const EYE_HEIGHT: f32 = 1.0;
+
+// Upward velocity applied when jumping
+const JUMP_SPEED: f32 = 5.0;
+
+// How far below the player's centre counts as standing on ground
+// Player is 2.0 tall with 0.1 rounding, so leave a small margin past the feet
+const GROUND_CHECK_DISTANCE: f32 = 1.2;
 
 // Handle player movement
 pub fn handle_player_movement(
     physics_world: &mut PhysicsWorld,
-    rl: &RaylibHandle,
+    actions: &ActionState,
     body_handle: &BodyHandle,
     yaw: f32,
 ) {
@@ -20,49 +33,64 @@ pub fn handle_player_movement(
         let forward = build_camera_forward(yaw, 0.0);
         let right = vector![-forward.z, 0.0, forward.x];
 
-        // Keys
-        if rl.is_key_down(KEY_W) {
-            movement += forward;
-        }
-        if rl.is_key_down(KEY_S) {
-            movement -= forward;
-        }
-        if rl.is_key_down(KEY_A) {
-            movement -= right;
-        }
-        if rl.is_key_down(KEY_D) {
-            movement += right;
-        }
+        // Actions
+        movement += forward * actions.forward_axis();
+        movement += right * actions.strafe_axis();
 
         // If player is moving (AKA not zero)
         if movement != RapierVector3::zeros() {
-            movement = movement.normalize() * 4.0; // Preserves direction, constant movement speed of 4
+            // Preserves direction, constant movement speed
+            let speed = if actions.is_down(Action::Sprint) {
+                SPRINT_SPEED
+            } else {
+                WALK_SPEED
+            };
+            movement = movement.normalize() * speed;
             let current_vel = body.linvel(); // Get current velocity
             body.set_linvel(vector![movement.x, current_vel.y, movement.z], true);
             // Apply new velocity only on X/Z axes
         }
     }
+
+    // Jump if standing on something
+    if actions.is_pressed(Action::Jump)
+        && is_grounded(physics_world, body_handle)
+        && let Some(body) = physics_world.bodies.get_mut(body_handle.body_handle)
+    {
+        let current_vel = *body.linvel();
+        body.set_linvel(vector![current_vel.x, JUMP_SPEED, current_vel.z], true);
+    }
 }
 
-// Update camera
-pub fn update_camera(
-    camera: &mut Camera3D,
-    physics_world: &PhysicsWorld,
-    body_handle: &BodyHandle,
-    yaw: f32,
-    pitch: f32,
-) {
-    if let Some(body) = physics_world.bodies.get(body_handle.body_handle) {
-        let position = body.translation();
-        camera.position = Vector3::new(position.x, position.y + 1.0, position.z);
+// Check if there's ground directly under the player
+fn is_grounded(physics_world: &PhysicsWorld, body_handle: &BodyHandle) -> bool {
+    let Some(body) = physics_world.bodies.get(body_handle.body_handle) else {
+        return false;
+    };
 
-        let forward = build_camera_forward(yaw, pitch);
-        camera.target = camera.position + Vector3::new(forward.x, forward.y, forward.z);
-    }
+    // Cast a ray down from the player's centre, ignoring the player
+    let position = body.translation();
+    let ray = Ray::new(
+        point![position.x, position.y, position.z],
+        vector![0.0, -1.0, 0.0],
+    );
+    let filter = QueryFilter::default().exclude_rigid_body(body_handle.body_handle);
+
+    physics_world
+        .query_pipeline
+        .cast_ray(
+            &physics_world.bodies,
+            &physics_world.colliders,
+            &ray,
+            GROUND_CHECK_DISTANCE,
+            true,
+            filter,
+        )
+        .is_some()
 }
 
 // Compute camera forward facing direction
-fn build_camera_forward(yaw: f32, pitch: f32) -> RapierVector3<f32> {
+pub fn build_camera_forward(yaw: f32, pitch: f32) -> RapierVector3<f32> {
     let x = yaw.cos() * pitch.cos();
     let y = pitch.sin();
     let z = yaw.sin() * pitch.cos();
@@ -87,11 +115,10 @@ impl MouseLook {
         }
     }
 
-    // Get mouse info
-    pub fn update_from_mouse(&mut self, rl: &RaylibHandle) {
-        let mouse_delta = rl.get_mouse_delta();
-        self.yaw += mouse_delta.x * self.mouse_sensitivity;
-        self.pitch -= mouse_delta.y * self.mouse_sensitivity;
+    // Apply look delta (e.g. mouse movement)
+    pub fn apply_look(&mut self, delta_x: f32, delta_y: f32) {
+        self.yaw += delta_x * self.mouse_sensitivity;
+        self.pitch -= delta_y * self.mouse_sensitivity;
         self.pitch = self.pitch.clamp(
             -std::f32::consts::FRAC_PI_2 + 0.01,
             std::f32::consts::FRAC_PI_2 - 0.01,
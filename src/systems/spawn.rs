@@ -1,37 +1,96 @@
 use crate::components::*;
 use crate::physics::*;
-use crate::systems::ai::*;
 use crate::world::grid::*;
-use hecs::{Bundle, World};
+use crate::world::loader::MapIssue;
+use crate::world::spawns::*;
+use hecs::{Bundle, Entity, World};
 use rand::seq::IndexedRandom;
-use rand::{rngs::ThreadRng, Rng};
+use rand::{Rng, rngs::StdRng};
 use rapier3d::prelude::*;
-use raylib::prelude::*;
+use std::collections::{HashSet, VecDeque};
+
+// Contact force above which a ball reports an impact
+const BALL_IMPACT_FORCE: f32 = 50.0;
+
+// Rounding on the edges of player and witch colliders
+const BODY_ROUNDING: f32 = 0.1;
+
+// Gap left under items so they float above the ground
+const ITEM_FLOAT_HEIGHT: f32 = 0.2;
+
+// Random spawns are kept this many tiles in from the edge of the map
+pub const SPAWN_MARGIN: usize = 2;
+
+// Check the map is big enough to pick random spawn positions inside the margin
+pub fn can_spawn_randomly(grid: &Grid) -> bool {
+    grid.width > SPAWN_MARGIN * 2 && grid.height > SPAWN_MARGIN * 2
+}
 
 // Generate entities
+// Fails if the map is too small to pick random positions on
 fn generate_entities<F, B>(
     ecs_world: &mut World,
     physics_world: &mut PhysicsWorld,
     grid: &Grid,
+    rng: &mut StdRng,
     entity_count: u32,
     mut generator_fn: F,
-) where
-    F: FnMut(&mut GridCoord, &mut PhysicsWorld, &mut ThreadRng) -> (B, Option<ColliderHandle>),
+) -> Result<(), MapIssue>
+where
+    F: FnMut(&mut GridCoord, &mut PhysicsWorld, &mut StdRng) -> (B, Option<ColliderHandle>),
     B: Bundle,
 {
-    //RNG
-    let mut rng = rand::rng();
+    if entity_count == 0 {
+        return Ok(());
+    }
+
+    let too_small = MapIssue::TooSmallForRandomSpawns {
+        width: grid.width,
+        height: grid.height,
+    };
+
+    if !can_spawn_randomly(grid) {
+        return Err(too_small);
+    }
+
+    // Columns already taken by bodies
+    let mut occupied: HashSet<(usize, usize)> = ecs_world
+        .query::<&BodyHandle>()
+        .iter()
+        .filter_map(|(_, body_handle)| physics_world.bodies.get(body_handle.body_handle))
+        .map(|body| {
+            let converted_pos = GridCoord::from_rapier3d_vec(*body.translation());
+            (converted_pos.x, converted_pos.z)
+        })
+        .collect();
+
+    // Random positions are rounded up, so they land one past the margin up to the margin in from the far edge
+    // Fail rather than retry forever if there aren't enough free columns there
+    let free_columns = (SPAWN_MARGIN + 1..=grid.width - SPAWN_MARGIN)
+        .flat_map(|x| (SPAWN_MARGIN + 1..=grid.height - SPAWN_MARGIN).map(move |z| (x, z)))
+        .filter(|column| !occupied.contains(column))
+        .count();
+    if free_columns < entity_count as usize {
+        return Err(too_small);
+    }
+
+    // Margin from edge of World
+    let margin = SPAWN_MARGIN as f32;
 
     // Num of entities generated
     let mut generated = 0;
 
-    'outer: while generated < entity_count {
+    while generated < entity_count {
         // Generate random X and Z coords
-        // At least 2.0 in from edge of World
+        // At least the margin in from edge of World
         // Rounded up to match grid usize
-        let x: f32 = rng.random_range(2.0..(grid.width as f32 - 2.0)).ceil();
+        let x: f32 = rng
+            .random_range(margin..(grid.width as f32 - margin))
+            .ceil();
         let y: f32 = 0.0;
-        let z: f32 = rng.random_range(2.0..(grid.height as f32 - 2.0)).ceil();
+        let z: f32 = rng
+            .random_range(margin..(grid.height as f32 - margin))
+            .ceil();
 
         // Create position with generated coords
         let mut position = GridCoord {
@@ -42,173 +101,220 @@ fn generate_entities<F, B>(
 
         // Create entity component bundle and collider
         // Exposes 'postition', 'physics_world', and 'rng' variables out to the closure
-        let (entity_bundle, maybe_collider) = generator_fn(&mut position, physics_world, &mut rng);
-
-        // Check no bodies in the way at spawn position
-        for (_, body_handle) in ecs_world.query::<&BodyHandle>().iter() {
-            if let Some(body) = physics_world.bodies.get(body_handle.body_handle) {
-                // Get body position from physics world
-                let pos = body.translation();
-
-                // Convert body positon to grid coordinates
-                let converted_pos = GridCoord::from_rapier3d_vec(*pos);
-
-                // Check and skip this spawn if blocked
-                if position == converted_pos {
-                    continue 'outer;
-                }
+        let (entity_bundle, maybe_collider) = generator_fn(&mut position, physics_world, rng);
+
+        // Check and skip this spawn if its column is taken, removing the body made for it
+        if !occupied.insert((position.x, position.z)) {
+            if let Some(collider_handle) = maybe_collider
+                && let Some(parent) = physics_world
+                    .colliders
+                    .get(collider_handle)
+                    .and_then(|collider| collider.parent())
+            {
+                physics_world.remove_body(parent);
             }
+
+            continue;
         }
 
         // Spawn entity into the world
-        let entity = ecs_world.spawn(entity_bundle);
-
-        // If entity has a collider, set collider's user_data field to ECS entity ID
-        if let Some(collider_handle) = maybe_collider {
-            if let Some(collider) = physics_world.colliders.get_mut(collider_handle) {
-                collider.user_data = entity.to_bits().get() as u128;
-            }
-        }
+        spawn_entity(ecs_world, physics_world, entity_bundle, maybe_collider);
 
         // Increment num of entities generated
         generated += 1;
     }
+
+    Ok(())
 }
 
-// Generate player
-pub fn generate_player(
+// Place entities at positions set in the map
+fn place_entities<F, B>(
     ecs_world: &mut World,
     physics_world: &mut PhysicsWorld,
-    grid: &Grid,
-) -> GridCoord {
-    // Create player start position initialized to zero
-    let mut player_start_position = GridCoord::zero();
+    rng: &mut StdRng,
+    positions: &[GridCoord],
+    mut generator_fn: F,
+) where
+    F: FnMut(&mut GridCoord, &mut PhysicsWorld, &mut StdRng) -> (B, Option<ColliderHandle>),
+    B: Bundle,
+{
+    for position in positions {
+        let mut position = *position;
 
-    generate_entities(ecs_world, physics_world, grid, 1, |position, p_world, _| {
-        // Set player start position to generated start position
-        player_start_position = *position;
+        let (entity_bundle, maybe_collider) = generator_fn(&mut position, physics_world, rng);
 
-        // Set player width and height
-        let width = grid.tile_size;
-        let height = grid.tile_size * 2.0;
-        let depth = grid.tile_size;
+        spawn_entity(ecs_world, physics_world, entity_bundle, maybe_collider);
+    }
+}
 
-        // Set spawn height
-        position.y = 2;
+// Place entities at positions set in the map, or generate them randomly if none were set
+fn place_or_generate_entities<F, B>(
+    ecs_world: &mut World,
+    physics_world: &mut PhysicsWorld,
+    grid: &Grid,
+    rng: &mut StdRng,
+    positions: &[GridCoord],
+    entity_count: u32,
+    generator_fn: F,
+) -> Result<(), MapIssue>
+where
+    F: FnMut(&mut GridCoord, &mut PhysicsWorld, &mut StdRng) -> (B, Option<ColliderHandle>),
+    B: Bundle,
+{
+    if positions.is_empty() {
+        generate_entities(
+            ecs_world,
+            physics_world,
+            grid,
+            rng,
+            entity_count,
+            generator_fn,
+        )
+    } else {
+        place_entities(ecs_world, physics_world, rng, positions, generator_fn);
+        Ok(())
+    }
+}
 
-        // Create body
-        let body = RigidBodyBuilder::dynamic()
-            .translation(position.to_rapier3d_vec(grid.tile_size))
-            .lock_rotations()
-            .linear_damping(4.0) // Slow down when keys are released
-            .ccd_enabled(true)
-            .build();
+// Spawn entity into the world
+// If entity has a collider, set collider's user_data field to ECS entity ID
+pub fn spawn_entity<B: Bundle>(
+    ecs_world: &mut World,
+    physics_world: &mut PhysicsWorld,
+    entity_bundle: B,
+    maybe_collider: Option<ColliderHandle>,
+) -> Entity {
+    let entity = ecs_world.spawn(entity_bundle);
+
+    if let Some(collider_handle) = maybe_collider
+        && let Some(collider) = physics_world.colliders.get_mut(collider_handle)
+    {
+        collider.user_data = entity.to_bits().get() as u128;
+    }
 
-        // Insert body into physics world and create BodyHandle component
-        let body_handle = BodyHandle {
-            body_handle: p_world.bodies.insert(body),
-        };
+    entity
+}
 
-        // Create collider
-        let collider =
-            ColliderBuilder::round_cuboid(width / 2.0, height / 2.0, depth / 2.0, 0.1).build();
+// Generate player
+pub fn generate_player(
+    ecs_world: &mut World,
+    physics_world: &mut PhysicsWorld,
+    grid: &Grid,
+    rng: &mut StdRng,
+    start: Option<GridCoord>,
+) -> Result<GridCoord, MapIssue> {
+    // Create player start position initialized to zero
+    let mut player_start_position = GridCoord::zero();
 
-        // Insert collider into physics world, attach it to body, and get collider handle
-        let collider_handle = p_world.colliders.insert_with_parent(
-            collider,
-            body_handle.body_handle,
-            &mut p_world.bodies,
-        );
+    place_or_generate_entities(
+        ecs_world,
+        physics_world,
+        grid,
+        rng,
+        start.as_slice(),
+        1,
+        |position, p_world, _| {
+            // Set player start position to generated start position
+            player_start_position = *position;
 
-        // Create Player component
-        let player = Player {};
+            // Stand on the ground
+            let translation = resting_translation(grid, position, grid.tile_size + BODY_ROUNDING);
 
-        // Return component bundle and collider handle
-        ((player, body_handle), Some(collider_handle))
-    });
+            create_player(p_world, grid.tile_size, translation)
+        },
+    )?;
 
     // Return player start position
-    player_start_position
+    Ok(player_start_position)
 }
 
 // Generate trees
+// Trees placed in the map keep the properties set there, the rest are picked randomly
 pub fn generate_trees(
     ecs_world: &mut World,
     physics_world: &mut PhysicsWorld,
     grid: &Grid,
+    rng: &mut StdRng,
     num_of_trees: u32,
-) {
-    generate_entities(
-        ecs_world,
-        physics_world,
-        grid,
-        num_of_trees,
-        |position, p_world, rng| {
-            // Generate random tree size
-            let widths = [1.0, 3.0];
-            let heights = [3.0, 5.0, 7.0];
-
-            let leaf_width: f32 = *widths.choose(rng).unwrap();
-            let leaf_height: f32 = *heights.choose(rng).unwrap();
-            let trunk_height: f32 = 1.0;
-
-            // Generate random color
-            let color_picker = rng.random_range(0..=1);
-
-            // Apply tree color
-            let (leaf_color, trunk_color) = match color_picker {
-                0 => (Color::GREEN, Color::BROWN),
-                1 => (Color::DARKGREEN, Color::DARKBROWN),
-                _ => (Color::GRAY, Color::GRAY),
-            };
+    placed: &[TreeSpawn],
+) -> Result<(), MapIssue> {
+    if placed.is_empty() {
+        return generate_entities(
+            ecs_world,
+            physics_world,
+            grid,
+            rng,
+            num_of_trees,
+            |position, p_world, rng| {
+                let tree = random_tree(rng);
+                let translation = tree_translation(grid, position, &tree);
+
+                create_tree(p_world, translation, tree)
+            },
+        );
+    }
 
-            // Get tree size for collider
-            let half_width = leaf_width / 2.0;
-            let total_height = trunk_height + leaf_height;
-
-            // Set spawn height
-            position.y = 0;
-
-            // Create body
-            // Center the collider vertically
-            let body = RigidBodyBuilder::fixed()
-                .translation(position.to_rapier3d_vec_new(
-                    position.x,
-                    (total_height / 2.0) as usize,
-                    position.z,
-                    grid.tile_size,
-                ))
-                .build();
-
-            // Insert body into physics world and create BodyHandle component
-            let body_handle = BodyHandle {
-                body_handle: p_world.bodies.insert(body),
-            };
+    for spawn in placed {
+        let mut tree = random_tree(rng);
+        spawn.apply(&mut tree);
 
-            // Create collider
-            let collider =
-                ColliderBuilder::cuboid(half_width, total_height / 2.0, half_width).build();
-
-            // Insert collider into physics world, attach it to body, and get collider handle
-            let collider_handle = p_world.colliders.insert_with_parent(
-                collider,
-                body_handle.body_handle,
-                &mut p_world.bodies,
-            );
-
-            // Create Tree component
-            let tree = Tree {
-                leaf_width,
-                leaf_height,
-                trunk_height,
-                leaf_color,
-                trunk_color,
-            };
+        let mut position = spawn.coord;
+        let translation = tree_translation(grid, &mut position, &tree);
+        let (bundle, collider) = create_tree(physics_world, translation, tree);
 
-            // Return component bundle and collider handle
-            ((tree, body_handle), Some(collider_handle))
-        },
-    );
+        spawn_entity(ecs_world, physics_world, bundle, collider);
+    }
+
+    Ok(())
+}
+
+// Generate a tree with random size and color
+fn random_tree(rng: &mut StdRng) -> Tree {
+    // Generate random tree size
+    let widths = [1.0, 3.0];
+    let heights = [3.0, 5.0, 7.0];
+
+    let leaf_width: f32 = *widths.choose(rng).unwrap();
+    let leaf_height: f32 = *heights.choose(rng).unwrap();
+    let trunk_height: f32 = 1.0;
+
+    // Generate random color
+    let color_picker = rng.random_range(0..=1);
+
+    // Apply tree color
+    let (leaf_color, trunk_color) = match color_picker {
+        0 => (Rgba::GREEN, Rgba::BROWN),
+        1 => (Rgba::DARKGREEN, Rgba::DARKBROWN),
+        _ => (Rgba::GRAY, Rgba::GRAY),
+    };
+
+    // Create Tree component
+    Tree {
+        leaf_width,
+        leaf_height,
+        trunk_height,
+        leaf_color,
+        trunk_color,
+    }
+}
+
+// Get tree spawn position
+// Center the collider vertically over the ground
+fn tree_translation(grid: &Grid, position: &mut GridCoord, tree: &Tree) -> Vector<f32> {
+    resting_translation(grid, position, (tree.trunk_height + tree.leaf_height) / 2.0)
+}
+
+// Get translation for a body resting on the ground at a position
+// Moves the position up to the first free level above the ground
+fn resting_translation(grid: &Grid, position: &mut GridCoord, half_height: f32) -> Vector<f32> {
+    let level = grid.surface_height(position.x, position.z);
+    let mut translation =
+        position.to_rapier3d_vec_new(position.x, level, position.z, grid.tile_size);
+
+    // Tiles are centred on their coordinates, so the ground's top is half a tile below
+    translation.y += half_height - grid.tile_size / 2.0;
+
+    translation
 }
 
 // Generate balls
@@ -216,54 +322,30 @@ pub fn generate_balls(
     ecs_world: &mut World,
     physics_world: &mut PhysicsWorld,
     grid: &Grid,
+    rng: &mut StdRng,
     num_of_balls: u32,
-) {
-    generate_entities(
+    placed: &[GridCoord],
+) -> Result<(), MapIssue> {
+    place_or_generate_entities(
         ecs_world,
         physics_world,
         grid,
+        rng,
+        placed,
         num_of_balls,
         |position, p_world, _| {
-            // Ball size
-            let ball_size = 0.5;
-
-            // Set spawn height
-            position.y = 10;
-
-            // Create body
-            let body = RigidBodyBuilder::dynamic()
-                .translation(position.to_rapier3d_vec(grid.tile_size))
-                .ccd_enabled(true)
-                .build();
-
-            // Insert body into physics world and create BodyHandle component
-            let body_handle = BodyHandle {
-                body_handle: p_world.bodies.insert(body),
-            };
-
-            // Create collider
-            let collider = ColliderBuilder::ball(ball_size)
-                .density(1.0)
-                .restitution(0.7)
-                .build();
-
-            // Insert collider into physics world, attach it to body, and get collider handle
-            let collider_handle = p_world.colliders.insert_with_parent(
-                collider,
-                body_handle.body_handle,
-                &mut p_world.bodies,
-            );
-
             // Create Ball component
             let ball = crate::components::Ball {
-                size: ball_size,
-                color: Color::BLUE,
+                size: 0.5,
+                color: Rgba::BLUE,
             };
 
-            // Return component bundle and collider handle
-            ((ball, body_handle), Some(collider_handle))
+            // Rest on the ground
+            let translation = resting_translation(grid, position, ball.size);
+
+            create_ball(p_world, translation, ball)
         },
-    );
+    )
 }
 
 // Generate witches
@@ -271,56 +353,362 @@ pub fn generate_witches(
     ecs_world: &mut World,
     physics_world: &mut PhysicsWorld,
     grid: &Grid,
+    rng: &mut StdRng,
     num_of_witches: u32,
-) {
-    generate_entities(
+    placed: &[GridCoord],
+) -> Result<(), MapIssue> {
+    place_or_generate_entities(
         ecs_world,
         physics_world,
         grid,
+        rng,
+        placed,
         num_of_witches,
         |position, p_world, _| {
-            // Set witch width and height
-            let width = grid.tile_size;
-            let height = grid.tile_size * 2.0;
-            let depth = grid.tile_size;
-
-            // Set spawn height
-            position.y = 2;
-
-            // Create body
-            let body = RigidBodyBuilder::dynamic()
-                .translation(position.to_rapier3d_vec(grid.tile_size))
-                .ccd_enabled(true)
-                .build();
-
-            // Insert body into physics world and create BodyHandle component
-            let body_handle = BodyHandle {
-                body_handle: p_world.bodies.insert(body),
-            };
-
-            // Create collider
-            let collider =
-                ColliderBuilder::round_cuboid(width / 2.0, height / 2.0, depth / 2.0, 0.1).build();
-
-            // Insert collider into physics world, attach it to body, and get collider handle
-            let collider_handle = p_world.colliders.insert_with_parent(
-                collider,
-                body_handle.body_handle,
-                &mut p_world.bodies,
-            );
-
-            // Create witch
-            let witch = Witch {
-                width,
-                height,
-                collider_handle,
-                color: Color::PURPLE,
-                state: WitchState::Patrolling,
-                target: generate_patrol_point(),
-            };
+            // Stand on the ground
+            let translation = resting_translation(grid, position, grid.tile_size + BODY_ROUNDING);
 
-            // Return component bundle and collider handle
-            ((witch, body_handle), Some(collider_handle))
+            // Patrol points are picked once the navigation grid is built
+            create_witch(p_world, grid.tile_size, translation, translation)
         },
+    )
+}
+
+// Generate items to collect
+// Items placed in the map come first, random ones make up any shortfall
+pub fn generate_items(
+    ecs_world: &mut World,
+    physics_world: &mut PhysicsWorld,
+    grid: &Grid,
+    rng: &mut StdRng,
+    num_of_items: u32,
+    placed: &[GridCoord],
+) -> Result<(), MapIssue> {
+    let mut create = |position: &mut GridCoord, p_world: &mut PhysicsWorld, _: &mut StdRng| {
+        // Create Item component
+        let item = Item {
+            size: 0.3,
+            color: Rgba::GOLD,
+        };
+
+        // Float just above the ground
+        let translation = resting_translation(grid, position, item.size + ITEM_FLOAT_HEIGHT);
+
+        create_item(p_world, translation, item)
+    };
+
+    place_entities(ecs_world, physics_world, rng, placed, &mut create);
+
+    generate_entities(
+        ecs_world,
+        physics_world,
+        grid,
+        rng,
+        num_of_items.saturating_sub(placed.len() as u32),
+        &mut create,
+    )
+}
+
+// Create player body, collider, and components at a position
+pub fn create_player(
+    physics_world: &mut PhysicsWorld,
+    tile_size: f32,
+    translation: Vector<f32>,
+) -> ((Player, BodyHandle), Option<ColliderHandle>) {
+    // Set player width and height
+    let width = tile_size;
+    let height = tile_size * 2.0;
+    let depth = tile_size;
+
+    // Create body
+    let body = RigidBodyBuilder::dynamic()
+        .translation(translation)
+        .lock_rotations()
+        .linear_damping(4.0) // Slow down when keys are released
+        .ccd_enabled(true)
+        .build();
+
+    // Insert body into physics world and create BodyHandle component
+    let body_handle = BodyHandle {
+        body_handle: physics_world.bodies.insert(body),
+    };
+
+    // Create collider, reporting collisions
+    let collider =
+        ColliderBuilder::round_cuboid(width / 2.0, height / 2.0, depth / 2.0, BODY_ROUNDING)
+            .active_events(ActiveEvents::COLLISION_EVENTS)
+            .build();
+
+    // Insert collider into physics world, attach it to body, and get collider handle
+    let collider_handle = physics_world.colliders.insert_with_parent(
+        collider,
+        body_handle.body_handle,
+        &mut physics_world.bodies,
+    );
+
+    // Create Player component
+    let player = Player {};
+
+    // Return component bundle and collider handle
+    ((player, body_handle), Some(collider_handle))
+}
+
+// Create tree body and collider at a position
+pub fn create_tree(
+    physics_world: &mut PhysicsWorld,
+    translation: Vector<f32>,
+    tree: Tree,
+) -> ((Tree, BodyHandle), Option<ColliderHandle>) {
+    // Get tree size for collider
+    let half_width = tree.leaf_width / 2.0;
+    let total_height = tree.trunk_height + tree.leaf_height;
+
+    // Create body
+    let body = RigidBodyBuilder::fixed().translation(translation).build();
+
+    // Insert body into physics world and create BodyHandle component
+    let body_handle = BodyHandle {
+        body_handle: physics_world.bodies.insert(body),
+    };
+
+    // Create collider
+    let collider = ColliderBuilder::cuboid(half_width, total_height / 2.0, half_width).build();
+
+    // Insert collider into physics world, attach it to body, and get collider handle
+    let collider_handle = physics_world.colliders.insert_with_parent(
+        collider,
+        body_handle.body_handle,
+        &mut physics_world.bodies,
+    );
+
+    // Return component bundle and collider handle
+    ((tree, body_handle), Some(collider_handle))
+}
+
+// Create ball body and collider at a position
+pub fn create_ball(
+    physics_world: &mut PhysicsWorld,
+    translation: Vector<f32>,
+    ball: crate::components::Ball,
+) -> (
+    (crate::components::Ball, BodyHandle),
+    Option<ColliderHandle>,
+) {
+    // Create body
+    let body = RigidBodyBuilder::dynamic()
+        .translation(translation)
+        .ccd_enabled(true)
+        .build();
+
+    // Insert body into physics world and create BodyHandle component
+    let body_handle = BodyHandle {
+        body_handle: physics_world.bodies.insert(body),
+    };
+
+    // Create collider, reporting collisions and hard impacts
+    let collider = ColliderBuilder::ball(ball.size)
+        .density(1.0)
+        .restitution(0.7)
+        .active_events(ActiveEvents::COLLISION_EVENTS | ActiveEvents::CONTACT_FORCE_EVENTS)
+        .contact_force_event_threshold(BALL_IMPACT_FORCE)
+        .build();
+
+    // Insert collider into physics world, attach it to body, and get collider handle
+    let collider_handle = physics_world.colliders.insert_with_parent(
+        collider,
+        body_handle.body_handle,
+        &mut physics_world.bodies,
+    );
+
+    // Return component bundle and collider handle
+    ((ball, body_handle), Some(collider_handle))
+}
+
+// Create item body and sensor collider at a position
+pub fn create_item(
+    physics_world: &mut PhysicsWorld,
+    translation: Vector<f32>,
+    item: Item,
+) -> ((Item, BodyHandle), Option<ColliderHandle>) {
+    // Create body
+    let body = RigidBodyBuilder::fixed().translation(translation).build();
+
+    // Insert body into physics world and create BodyHandle component
+    let body_handle = BodyHandle {
+        body_handle: physics_world.bodies.insert(body),
+    };
+
+    // Create sensor collider, reporting when the player walks through it
+    let collider = ColliderBuilder::ball(item.size)
+        .sensor(true)
+        .active_events(ActiveEvents::COLLISION_EVENTS)
+        .build();
+
+    // Insert collider into physics world, attach it to body, and get collider handle
+    let collider_handle = physics_world.colliders.insert_with_parent(
+        collider,
+        body_handle.body_handle,
+        &mut physics_world.bodies,
+    );
+
+    // Return component bundle and collider handle
+    ((item, body_handle), Some(collider_handle))
+}
+
+// Create witch body, collider, and components at a position, patrolling to a target
+pub fn create_witch(
+    physics_world: &mut PhysicsWorld,
+    tile_size: f32,
+    translation: Vector<f32>,
+    target: Vector<f32>,
+) -> ((Witch, BodyHandle), Option<ColliderHandle>) {
+    // Set witch width and height
+    let width = tile_size;
+    let height = tile_size * 2.0;
+    let depth = tile_size;
+
+    // Create body
+    let body = RigidBodyBuilder::dynamic()
+        .translation(translation)
+        .ccd_enabled(true)
+        .build();
+
+    // Insert body into physics world and create BodyHandle component
+    let body_handle = BodyHandle {
+        body_handle: physics_world.bodies.insert(body),
+    };
+
+    // Create collider, reporting collisions
+    let collider =
+        ColliderBuilder::round_cuboid(width / 2.0, height / 2.0, depth / 2.0, BODY_ROUNDING)
+            .active_events(ActiveEvents::COLLISION_EVENTS)
+            .build();
+
+    // Insert collider into physics world, attach it to body, and get collider handle
+    let collider_handle = physics_world.colliders.insert_with_parent(
+        collider,
+        body_handle.body_handle,
+        &mut physics_world.bodies,
     );
+
+    // Create witch
+    let witch = Witch {
+        width,
+        height,
+        collider_handle,
+        color: Rgba::PURPLE,
+        state: WitchState::Patrol,
+        state_time: 0.0,
+        target,
+        route: None,
+        waypoint: 0,
+        home: translation,
+        facing: vector![0.0, 0.0, 1.0],
+        last_known_position: None,
+        goal_unreachable: false,
+        path: VecDeque::new(),
+        path_goal: None,
+    };
+
+    // Return component bundle and collider handle
+    ((witch, body_handle), Some(collider_handle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn random_spawns_on_a_tiny_map_fail_instead_of_panicking() {
+        let mut ecs_world = World::new();
+        let mut physics_world = PhysicsWorld::new();
+        let mut rng = StdRng::seed_from_u64(0);
+        let grid = Grid::new(4, 4, 1);
+
+        let result = generate_balls(&mut ecs_world, &mut physics_world, &grid, &mut rng, 3, &[]);
+        assert!(matches!(
+            result,
+            Err(MapIssue::TooSmallForRandomSpawns {
+                width: 4,
+                height: 4
+            })
+        ));
+
+        // Placed spawns don't need room for random ones
+        let placed = [GridCoord { x: 1, y: 0, z: 1 }];
+        let result = generate_balls(
+            &mut ecs_world,
+            &mut physics_world,
+            &grid,
+            &mut rng,
+            3,
+            &placed,
+        );
+        assert!(result.is_ok());
+        assert_eq!(ecs_world.len(), 1);
+    }
+
+    #[test]
+    fn more_random_spawns_than_free_columns_fail_instead_of_hanging() {
+        let mut ecs_world = World::new();
+        let mut physics_world = PhysicsWorld::new();
+        let mut rng = StdRng::seed_from_u64(0);
+        let grid = Grid::new(6, 6, 1);
+
+        // Only the columns 3 and 4 along each axis are picked on a 6x6 map
+        let result = generate_trees(&mut ecs_world, &mut physics_world, &grid, &mut rng, 4, &[]);
+        assert!(result.is_ok());
+        assert_eq!(ecs_world.len(), 4);
+
+        let result = generate_balls(&mut ecs_world, &mut physics_world, &grid, &mut rng, 1, &[]);
+        assert!(matches!(
+            result,
+            Err(MapIssue::TooSmallForRandomSpawns {
+                width: 6,
+                height: 6
+            })
+        ));
+        assert_eq!(ecs_world.len(), 4);
+    }
+
+    #[test]
+    fn placed_player_stands_on_raised_ground() {
+        let mut ecs_world = World::new();
+        let mut physics_world = PhysicsWorld::new();
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut grid = Grid::new(8, 8, 1);
+        grid.tile_size = 1.0;
+
+        // A column three tiles high under the start
+        for y in 0..3 {
+            grid.set(Tile {
+                kind: TileType::Stone,
+                coord: GridCoord { x: 3, y, z: 3 },
+            });
+        }
+
+        let start = GridCoord { x: 3, y: 0, z: 3 };
+        generate_player(
+            &mut ecs_world,
+            &mut physics_world,
+            &grid,
+            &mut rng,
+            Some(start),
+        )
+        .unwrap();
+
+        let (_, (_, body_handle)) = ecs_world
+            .query_mut::<(&Player, &BodyHandle)>()
+            .into_iter()
+            .next()
+            .unwrap();
+        let body = &physics_world.bodies[body_handle.body_handle];
+
+        // Top of the column is at 2.5, the player's half height is a tile plus rounding
+        let expected = 2.5 + grid.tile_size + BODY_ROUNDING;
+        assert!((body.translation().y - expected).abs() < 1e-5);
+        assert_eq!(body.translation().x, 3.0);
+        assert_eq!(body.translation().z, 3.0);
+    }
 }
@@ -1,90 +1,252 @@
 use crate::components::*;
 use crate::physics::*;
 use crate::world::grid::*;
-use hecs::{Bundle, World};
+use rapier3d::parry::shape::Voxels;
 use rapier3d::prelude::*;
-use raylib::prelude::*;
-
-// Generate blocks
-pub fn generate_blocks(ecs_world: &mut World, physics_world: &mut PhysicsWorld, grid: &Grid) {
-    for row in &grid.tiles {
-        for tile in row {
-            match tile.kind {
-                TileType::Grass => {
-                    generate_block(ecs_world, physics_world, grid, tile, |body_handle| {
-                        let grass = Block {
-                            width: grid.tile_size,
-                            height: grid.tile_size,
-                            color: Color::LIMEGREEN,
-                        };
-
-                        let body_handle = BodyHandle { body_handle };
-
-                        (grass, body_handle)
-                    });
-                }
-                TileType::Stone => {
-                    generate_block(ecs_world, physics_world, grid, tile, |body_handle| {
-                        let stone = Block {
-                            width: grid.tile_size,
-                            height: grid.tile_size,
-                            color: Color::DARKGRAY,
-                        };
-
-                        let body_handle = BodyHandle { body_handle };
-
-                        (stone, body_handle)
-                    });
-                }
-                _ => {}
+
+// How far away blocks can be dug or placed
+pub const BLOCK_REACH: f32 = 5.0;
+
+// Terrain
+// Every block in the grid merged into one fixed body with a voxel collider
+pub struct Terrain {
+    pub body_handle: RigidBodyHandle,
+    pub collider_handle: Option<ColliderHandle>,
+}
+
+// Functions for Terrain
+impl Terrain {
+    // Create terrain body and collider from the grid's blocks
+    pub fn new(physics_world: &mut PhysicsWorld, grid: &Grid) -> Self {
+        // Voxels are centered half a tile in from their corner, blocks are centered on their tile
+        let half_tile = grid.tile_size / 2.0;
+        let body = RigidBodyBuilder::fixed()
+            .translation(vector![-half_tile, -half_tile, -half_tile])
+            .build();
+
+        let mut terrain = Self {
+            body_handle: physics_world.bodies.insert(body),
+            collider_handle: None,
+        };
+
+        terrain.insert_collider(physics_world, grid);
+
+        terrain
+    }
+
+    // Update voxels for tiles that changed in the grid since the collider was built
+    pub fn sync(&mut self, physics_world: &mut PhysicsWorld, grid: &Grid) {
+        // Grid had no blocks before, so build the collider from scratch
+        let Some(collider) = self
+            .collider_handle
+            .and_then(|collider_handle| physics_world.colliders.get_mut(collider_handle))
+        else {
+            self.insert_collider(physics_world, grid);
+            return;
+        };
+
+        // Compare first, so the collider is only marked as changed if a voxel flips
+        let Some(voxels) = collider.shape().as_voxels() else {
+            return;
+        };
+
+        let changed: Vec<(Point<i32>, bool)> = grid
+            .tiles()
+            .map(|tile| (voxel_key(tile.coord), block_color(tile.kind).is_some()))
+            .filter(|(key, solid)| {
+                let empty = voxels
+                    .get_voxel_state(*key)
+                    .is_none_or(|state| state.is_empty());
+                empty == *solid
+            })
+            .collect();
+
+        if changed.is_empty() {
+            return;
+        }
+
+        if let Some(voxels) = collider.shape_mut().as_voxels_mut() {
+            for (key, solid) in changed {
+                voxels.set_voxel(key, solid);
             }
         }
     }
+
+    // Update the voxel for a single tile after it changes in the grid
+    pub fn update_tile(&mut self, physics_world: &mut PhysicsWorld, grid: &Grid, coord: GridCoord) {
+        let Some(collider) = self
+            .collider_handle
+            .and_then(|collider_handle| physics_world.colliders.get_mut(collider_handle))
+        else {
+            self.insert_collider(physics_world, grid);
+            return;
+        };
+
+        let solid = grid
+            .get(coord)
+            .is_some_and(|tile| block_color(tile.kind).is_some());
+
+        if let Some(voxels) = collider.shape_mut().as_voxels_mut() {
+            voxels.set_voxel(voxel_key(coord), solid);
+        }
+    }
+
+    // Find the block a ray hits first, if nothing else is in the way
+    pub fn target_block(
+        &self,
+        physics_world: &PhysicsWorld,
+        grid: &Grid,
+        ray: &Ray,
+        filter: QueryFilter,
+    ) -> Option<BlockTarget> {
+        let (collider_handle, hit) = physics_world.query_pipeline.cast_ray_and_get_normal(
+            &physics_world.bodies,
+            &physics_world.colliders,
+            ray,
+            BLOCK_REACH,
+            true,
+            filter,
+        )?;
+
+        if Some(collider_handle) != self.collider_handle {
+            return None;
+        }
+
+        // Step half a tile either side of the face that was hit
+        let point = ray.point_at(hit.time_of_impact);
+        let offset = hit.normal * grid.tile_size / 2.0;
+
+        Some(BlockTarget {
+            block: world_to_coord(point - offset, grid.tile_size)?,
+            in_front: world_to_coord(point + offset, grid.tile_size),
+        })
+    }
+
+    // Check if a tile is clear of everything but terrain, so a block can be placed in it
+    pub fn is_tile_clear(
+        &self,
+        physics_world: &PhysicsWorld,
+        grid: &Grid,
+        coord: GridCoord,
+    ) -> bool {
+        // Shrink the block a little so things resting against it don't count
+        let half_extent = grid.tile_size / 2.0 * 0.95;
+        let shape = Cuboid::new(Vector::repeat(half_extent));
+        let position = Isometry::translation(
+            coord.x as f32 * grid.tile_size,
+            coord.y as f32 * grid.tile_size,
+            coord.z as f32 * grid.tile_size,
+        );
+
+        let mut filter = QueryFilter::default();
+        if let Some(collider_handle) = self.collider_handle {
+            filter = filter.exclude_collider(collider_handle);
+        }
+
+        physics_world
+            .query_pipeline
+            .intersection_with_shape(
+                &physics_world.bodies,
+                &physics_world.colliders,
+                &position,
+                &shape,
+                filter,
+            )
+            .is_none()
+    }
+
+    // Insert voxel collider, unless the grid has no blocks to make it from
+    fn insert_collider(&mut self, physics_world: &mut PhysicsWorld, grid: &Grid) {
+        let keys: Vec<Point<i32>> = grid
+            .tiles()
+            .filter(|tile| block_color(tile.kind).is_some())
+            .map(|tile| voxel_key(tile.coord))
+            .collect();
+
+        if keys.is_empty() {
+            return;
+        }
+
+        let voxels = Voxels::new(Vector::repeat(grid.tile_size), &keys);
+        let collider = ColliderBuilder::new(SharedShape::new(voxels)).build();
+
+        self.collider_handle = Some(physics_world.colliders.insert_with_parent(
+            collider,
+            self.body_handle,
+            &mut physics_world.bodies,
+        ));
+    }
+}
+
+// Get the color of a tile's block, or None for tiles that aren't blocks
+pub fn block_color(kind: TileType) -> Option<Rgba> {
+    match kind {
+        TileType::Grass => Some(Rgba::LIMEGREEN),
+        TileType::Stone => Some(Rgba::DARKGRAY),
+        TileType::Exit => Some(Rgba::GOLD),
+        _ => None,
+    }
+}
+
+// Block target
+// A block being looked at, and the tile in front of the face being looked at
+pub struct BlockTarget {
+    pub block: GridCoord,
+    pub in_front: Option<GridCoord>,
 }
 
-// Generate block
-fn generate_block<F, B>(
-    ecs_world: &mut World,
-    physics_world: &mut PhysicsWorld,
-    grid: &Grid,
-    tile: &Tile,
-    mut generator_function: F,
-) where
-    F: FnMut(RigidBodyHandle) -> B,
-    B: Bundle,
-{
-    // Create body
-    let body = RigidBodyBuilder::fixed()
-        .translation(tile.coord.to_rapier3d_vec(grid.tile_size))
-        .build();
-
-    // Insert body into physics world and get body handle
-    let body_handle = physics_world.bodies.insert(body);
-
-    // Create collider
-    let collider = ColliderBuilder::cuboid(
-        grid.tile_size / 2.0,
-        grid.tile_size / 2.0,
-        grid.tile_size / 2.0,
-    )
-    .build();
-
-    // Insert collider into physics world, attach it to body, and get collider handle
-    let collider_handle = physics_world.colliders.insert_with_parent(
-        collider,
-        body_handle,
-        &mut physics_world.bodies,
-    );
-
-    // Create entity component bundle
-    // Exposes 'body_handle' variable out to the closure
-    let bundle = generator_function(body_handle);
-
-    // Spawn entity into the world
-    let entity = ecs_world.spawn(bundle);
-
-    // Set collider's user_data field to ECS entity ID
-    if let Some(collider) = physics_world.colliders.get_mut(collider_handle) {
-        collider.user_data = entity.to_bits().get() as u128;
+// Get grid coordinates of the tile containing a point, blocks are centered on their tile
+fn world_to_coord(point: Point<f32>, tile_size: f32) -> Option<GridCoord> {
+    let coord = (point.coords / tile_size).map(f32::round);
+
+    if coord.x < 0.0 || coord.y < 0.0 || coord.z < 0.0 {
+        return None;
+    }
+
+    Some(GridCoord {
+        x: coord.x as usize,
+        y: coord.y as usize,
+        z: coord.z as usize,
+    })
+}
+
+// Get voxel key for grid coordinates
+fn voxel_key(coord: GridCoord) -> Point<i32> {
+    point![coord.x as i32, coord.y as i32, coord.z as i32]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn body_rests_on_the_edge_of_the_voxel_domain() {
+        let mut physics_world = PhysicsWorld::new();
+        let mut grid = Grid::new(3, 3, 1);
+        grid.tile_size = 1.0;
+        grid.fill_area(GridCoord { x: 1, y: 0, z: 1 }, 1, TileType::Grass);
+
+        let terrain = Terrain::new(&mut physics_world, &grid);
+        assert!(terrain.collider_handle.is_some());
+
+        // Drop a player sized body onto the corner block, where contacts reach past the domain
+        // Only passes in debug builds with overflow checks off for parry, see Cargo.toml
+        let body = RigidBodyBuilder::dynamic()
+            .translation(vector![0.0, 2.0, 0.0])
+            .lock_rotations()
+            .build();
+        let body_handle = physics_world.bodies.insert(body);
+        let collider = ColliderBuilder::round_cuboid(0.5, 1.0, 0.5, 0.1).build();
+        physics_world
+            .colliders
+            .insert_with_parent(collider, body_handle, &mut physics_world.bodies);
+
+        for _ in 0..120 {
+            physics_world.step();
+        }
+
+        // Top of the blocks is half a tile up
+        let y = physics_world.bodies[body_handle].translation().y;
+        assert!((y - 1.6).abs() < 0.05, "body came to rest at {y}");
     }
 }
@@ -0,0 +1,373 @@
+use crate::components::*;
+use crate::settings::*;
+use crate::systems::terrain::*;
+use crate::world::grid::*;
+use raylib::ffi;
+use raylib::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+// Width and depth of a terrain chunk in tiles
+pub const CHUNK_SIZE: usize = 16;
+
+// Quad
+// Block faces merged into one rectangle, corners wound counter-clockwise seen from outside
+pub struct Quad {
+    pub corners: [[f32; 3]; 4],
+    pub normal: [f32; 3],
+    pub color: Rgba,
+}
+
+// Terrain models
+// Blocks baked into one model per chunk, uploaded once and rebaked when their tiles change
+pub struct TerrainModels {
+    models: HashMap<(usize, usize), Model>,
+    changed_chunks: HashSet<(usize, usize)>,
+}
+
+// Functions for TerrainModels
+impl TerrainModels {
+    // Mesh and upload every chunk of the grid
+    pub fn build(rl: &mut RaylibHandle, thread: &RaylibThread, grid: &Grid) -> Self {
+        let mut terrain = Self {
+            models: HashMap::new(),
+            changed_chunks: HashSet::new(),
+        };
+
+        for chunk_z in 0..grid.height.div_ceil(CHUNK_SIZE) {
+            for chunk_x in 0..grid.width.div_ceil(CHUNK_SIZE) {
+                terrain.build_chunk(rl, thread, grid, (chunk_x, chunk_z));
+            }
+        }
+
+        if debug_mode() {
+            println!(
+                "🧱 Terrain baked into {} chunk models",
+                terrain.models.len()
+            );
+        }
+
+        terrain
+    }
+
+    // Mark chunks as needing a rebake after tiles change
+    // Neighbouring chunks are included, as faces along their edge may be uncovered
+    pub fn mark_changed(&mut self, coords: &[GridCoord]) {
+        for coord in coords {
+            for (dx, dz) in [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let (Some(x), Some(z)) = (
+                    coord.x.checked_add_signed(dx),
+                    coord.z.checked_add_signed(dz),
+                ) else {
+                    continue;
+                };
+
+                self.changed_chunks.insert((x / CHUNK_SIZE, z / CHUNK_SIZE));
+            }
+        }
+    }
+
+    // Rebake chunks marked as changed
+    pub fn rebuild_changed(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, grid: &Grid) {
+        for chunk in std::mem::take(&mut self.changed_chunks) {
+            self.build_chunk(rl, thread, grid, chunk);
+        }
+    }
+
+    // Draw terrain
+    pub fn draw(&self, d3d: &mut RaylibMode3D<RaylibDrawHandle>) {
+        for model in self.models.values() {
+            d3d.draw_model(model, Vector3::new(0.0, 0.0, 0.0), 1.0, Color::WHITE);
+        }
+    }
+
+    // Mesh and upload a chunk, replacing its old model
+    fn build_chunk(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        grid: &Grid,
+        (chunk_x, chunk_z): (usize, usize),
+    ) {
+        self.models.remove(&(chunk_x, chunk_z));
+
+        let quads = mesh_chunk(grid, chunk_x, chunk_z);
+
+        // Nothing to draw in this chunk
+        if quads.is_empty() {
+            return;
+        }
+
+        match upload_quads(rl, thread, &quads) {
+            Ok(model) => {
+                self.models.insert((chunk_x, chunk_z), model);
+            }
+            Err(e) => eprintln!("Failed to upload terrain chunk ({chunk_x}, {chunk_z}): {e}"),
+        }
+    }
+}
+
+// Mesh a chunk of terrain using greedy meshing
+// Faces against another block are culled, and touching faces of the same color are merged
+pub fn mesh_chunk(grid: &Grid, chunk_x: usize, chunk_z: usize) -> Vec<Quad> {
+    let origin = [chunk_x * CHUNK_SIZE, 0, chunk_z * CHUNK_SIZE];
+    let size = [
+        CHUNK_SIZE.min(grid.width.saturating_sub(origin[0])),
+        grid.levels,
+        CHUNK_SIZE.min(grid.height.saturating_sub(origin[2])),
+    ];
+    let tile_size = grid.tile_size;
+    let half_tile = tile_size / 2.0;
+
+    // Get block color at a position in the chunk, neighbours may be outside it
+    let block_at = |position: [isize; 3]| -> Option<Rgba> {
+        let x = origin[0] as isize + position[0];
+        let y = origin[1] as isize + position[1];
+        let z = origin[2] as isize + position[2];
+
+        if x < 0 || y < 0 || z < 0 {
+            return None;
+        }
+
+        let coord = GridCoord {
+            x: x as usize,
+            y: y as usize,
+            z: z as usize,
+        };
+
+        grid.get(coord).and_then(|tile| block_color(tile.kind))
+    };
+
+    let mut quads = Vec::new();
+
+    // Sweep slices across the chunk along each axis, for faces pointing each way
+    for axis in 0..3 {
+        // Axes across the slice, ordered so u × v points along the axis
+        let u = (axis + 1) % 3;
+        let v = (axis + 2) % 3;
+
+        for direction in [1isize, -1] {
+            for slice in 0..size[axis] {
+                // Get colors of faces in this slice that aren't covered by a neighbour
+                let mut mask: Vec<Option<Rgba>> = vec![None; size[u] * size[v]];
+
+                for j in 0..size[v] {
+                    for i in 0..size[u] {
+                        let mut position = [0isize; 3];
+                        position[axis] = slice as isize;
+                        position[u] = i as isize;
+                        position[v] = j as isize;
+
+                        let mut neighbour = position;
+                        neighbour[axis] += direction;
+
+                        if block_at(neighbour).is_none() {
+                            mask[j * size[u] + i] = block_at(position);
+                        }
+                    }
+                }
+
+                // Merge faces into rectangles, growing along u then v
+                for j in 0..size[v] {
+                    let mut i = 0;
+
+                    while i < size[u] {
+                        let Some(color) = mask[j * size[u] + i] else {
+                            i += 1;
+                            continue;
+                        };
+
+                        let mut width = 1;
+                        while i + width < size[u] && mask[j * size[u] + i + width] == Some(color) {
+                            width += 1;
+                        }
+
+                        let mut height = 1;
+                        'grow: while j + height < size[v] {
+                            for k in 0..width {
+                                if mask[(j + height) * size[u] + i + k] != Some(color) {
+                                    break 'grow;
+                                }
+                            }
+                            height += 1;
+                        }
+
+                        // Clear merged faces so they aren't used again
+                        for dj in 0..height {
+                            for di in 0..width {
+                                mask[(j + dj) * size[u] + i + di] = None;
+                            }
+                        }
+
+                        // Get corner in world space, blocks are centered on their tile
+                        let corner = |du: usize, dv: usize| {
+                            let mut point = [0.0; 3];
+                            point[axis] = (origin[axis] + slice) as f32 * tile_size
+                                + direction as f32 * half_tile;
+                            point[u] = (origin[u] + i + du) as f32 * tile_size - half_tile;
+                            point[v] = (origin[v] + j + dv) as f32 * tile_size - half_tile;
+                            point
+                        };
+
+                        let corners = if direction > 0 {
+                            [
+                                corner(0, 0),
+                                corner(width, 0),
+                                corner(width, height),
+                                corner(0, height),
+                            ]
+                        } else {
+                            [
+                                corner(0, 0),
+                                corner(0, height),
+                                corner(width, height),
+                                corner(width, 0),
+                            ]
+                        };
+
+                        let mut normal = [0.0; 3];
+                        normal[axis] = direction as f32;
+
+                        quads.push(Quad {
+                            corners,
+                            normal,
+                            color,
+                        });
+
+                        i += width;
+                    }
+                }
+            }
+        }
+    }
+
+    quads
+}
+
+// Upload quads as a model, two triangles per quad
+fn upload_quads(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    quads: &[Quad],
+) -> Result<Model, String> {
+    let vertex_count = quads.len() * 6;
+
+    let mut vertices: Vec<f32> = Vec::with_capacity(vertex_count * 3);
+    let mut normals: Vec<f32> = Vec::with_capacity(vertex_count * 3);
+    let mut colors: Vec<u8> = Vec::with_capacity(vertex_count * 4);
+
+    for quad in quads {
+        for corner in [0, 1, 2, 0, 2, 3] {
+            vertices.extend(quad.corners[corner]);
+            normals.extend(quad.normal);
+            colors.extend([quad.color.r, quad.color.g, quad.color.b, quad.color.a]);
+        }
+    }
+
+    // Raylib frees the vertex data when the model is unloaded
+    let mut raw: ffi::Mesh = unsafe { std::mem::zeroed() };
+    raw.vertexCount = vertex_count as i32;
+    raw.triangleCount = (quads.len() * 2) as i32;
+    raw.vertices = raylib_copy(&vertices);
+    raw.normals = raylib_copy(&normals);
+    raw.colors = raylib_copy(&colors);
+
+    let mut mesh = unsafe { Mesh::from_raw(raw) };
+    unsafe { mesh.upload(false) };
+
+    // The model takes ownership of the mesh
+    rl.load_model_from_mesh(thread, unsafe { mesh.make_weak() })
+        .map_err(|e| e.to_string())
+}
+
+// Copy data into memory allocated by raylib
+fn raylib_copy<T: Copy>(data: &[T]) -> *mut T {
+    unsafe {
+        let pointer = ffi::MemAlloc(std::mem::size_of_val(data) as u32) as *mut T;
+        std::ptr::copy_nonoverlapping(data.as_ptr(), pointer, data.len());
+        pointer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_with_blocks(width: usize, blocks: &[(usize, TileType)]) -> Grid {
+        let mut grid = Grid::new(width, 1, 1);
+        grid.tile_size = 1.0;
+
+        for &(x, kind) in blocks {
+            grid.set(Tile {
+                kind,
+                coord: GridCoord { x, y: 0, z: 0 },
+            });
+        }
+
+        grid
+    }
+
+    // Quads facing along a normal
+    fn facing(quads: &[Quad], normal: [f32; 3]) -> Vec<&Quad> {
+        quads.iter().filter(|quad| quad.normal == normal).collect()
+    }
+
+    #[test]
+    fn faces_between_neighbouring_blocks_are_culled() {
+        // Different colors, so only culling can remove faces
+        let grid = grid_with_blocks(2, &[(0, TileType::Grass), (1, TileType::Stone)]);
+
+        let quads = mesh_chunk(&grid, 0, 0);
+
+        // Six faces each, less the two where they touch at x = 0.5
+        assert_eq!(quads.len(), 10);
+        for normal in [[1.0, 0.0, 0.0], [-1.0, 0.0, 0.0]] {
+            assert!(
+                facing(&quads, normal)
+                    .iter()
+                    .all(|quad| quad.corners[0][0] != 0.5)
+            );
+        }
+    }
+
+    #[test]
+    fn faces_at_a_chunk_edge_are_culled_against_the_next_chunk() {
+        let last = CHUNK_SIZE - 1;
+        let grid = grid_with_blocks(
+            CHUNK_SIZE * 2,
+            &[(last, TileType::Grass), (CHUNK_SIZE, TileType::Grass)],
+        );
+
+        let first_chunk = mesh_chunk(&grid, 0, 0);
+        let second_chunk = mesh_chunk(&grid, 1, 0);
+
+        assert_eq!(first_chunk.len(), 5);
+        assert!(facing(&first_chunk, [1.0, 0.0, 0.0]).is_empty());
+        assert_eq!(second_chunk.len(), 5);
+        assert!(facing(&second_chunk, [-1.0, 0.0, 0.0]).is_empty());
+    }
+
+    #[test]
+    fn coplanar_faces_of_the_same_color_merge() {
+        let grid = grid_with_blocks(
+            3,
+            &[
+                (0, TileType::Grass),
+                (1, TileType::Grass),
+                (2, TileType::Grass),
+            ],
+        );
+
+        let quads = mesh_chunk(&grid, 0, 0);
+
+        // Top, bottom, both long sides and both ends
+        assert_eq!(quads.len(), 6);
+
+        let top = facing(&quads, [0.0, 1.0, 0.0]);
+        assert_eq!(top.len(), 1);
+
+        let xs: Vec<f32> = top[0].corners.iter().map(|corner| corner[0]).collect();
+        assert_eq!(xs.iter().copied().fold(f32::INFINITY, f32::min), -0.5);
+        assert_eq!(xs.iter().copied().fold(f32::NEG_INFINITY, f32::max), 2.5);
+        assert!(top[0].corners.iter().all(|corner| corner[1] == 0.5));
+    }
+}
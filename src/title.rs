@@ -1,52 +1,237 @@
+use crate::editor::*;
 use crate::game::*;
-use crate::State;
+use crate::save::*;
+use crate::settings::*;
+use crate::simulation::*;
+use crate::world::maps::*;
+use crate::world::native::*;
+use crate::{State, Transition};
 use raylib::prelude::*;
 
-// Update
-pub fn update(rl: &mut RaylibHandle, next_state: &mut Option<State>) {
-    // If cursor is hidden then enable it
-    if rl.is_cursor_hidden() {
-        rl.enable_cursor();
+// Title screen
+pub struct TitleScreen {
+    pub maps: MapRegistry,
+    pub selected_map: usize,
+    // Why the last game failed to start
+    pub error: Option<String>,
+}
+
+// Functions for TitleScreen
+impl TitleScreen {
+    // Create title screen with the maps to pick from
+    pub fn new(maps: MapRegistry) -> Self {
+        Self {
+            maps,
+            selected_map: 0,
+            error: None,
+        }
     }
 
-    // Set text style for GUI
-    rl.gui_set_style(GuiControl::DEFAULT, GuiDefaultProperty::TEXT_SIZE, 30);
+    // Update
+    pub fn update(
+        &mut self,
+        rl: &mut RaylibHandle,
+        settings: &Settings,
+        transition: &mut Option<Transition>,
+    ) {
+        // If cursor is hidden then enable it
+        if rl.is_cursor_hidden() {
+            rl.enable_cursor();
+        }
+
+        // Set text style for GUI
+        rl.gui_set_style(GuiControl::DEFAULT, GuiDefaultProperty::TEXT_SIZE, 30);
+
+        // Press Left or Right to pick a map
+        if rl.is_key_pressed(KeyboardKey::KEY_LEFT) {
+            self.previous_map();
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_RIGHT) {
+            self.next_map();
+        }
+
+        // Press Enter or Space to continue
+        if rl.is_key_pressed(KeyboardKey::KEY_ENTER) || rl.is_key_pressed(KeyboardKey::KEY_SPACE) {
+            self.start_single_map(settings, transition);
+        }
+    }
+
+    // Render
+    pub fn render(
+        &mut self,
+        d: &mut RaylibDrawHandle,
+        settings: &Settings,
+        transition: &mut Option<Transition>,
+    ) {
+        // Clear frame
+        d.clear_background(Color::WHITE);
+
+        // Map picker
+        if self.maps.is_empty() {
+            d.draw_text(
+                &format!("No maps found in {MAPS_DIR}"),
+                250,
+                95,
+                20,
+                Color::RED,
+            );
+        } else {
+            if d.gui_button(Rectangle::new(250.0, 80.0, 50.0, 50.0), "<") {
+                self.previous_map();
+            }
+
+            let label = format!(
+                "{} ({}/{})",
+                self.maps.maps[self.selected_map].name,
+                self.selected_map + 1,
+                self.maps.len()
+            );
+            d.gui_label(Rectangle::new(310.0, 80.0, 180.0, 50.0), &label);
+
+            if d.gui_button(Rectangle::new(500.0, 80.0, 50.0, 50.0), ">") {
+                self.next_map();
+            }
+        }
+
+        // Start button
+        if d.gui_button(Rectangle::new(300.0, 150.0, 200.0, 50.0), "START") {
+            self.start_single_map(settings, transition);
+        }
+
+        // Campaign button
+        if d.gui_button(Rectangle::new(300.0, 210.0, 200.0, 50.0), "CAMPAIGN") {
+            self.start_campaign(settings, transition);
+        }
+
+        // Editor buttons, for the selected map or a new one
+        if !self.maps.is_empty() && d.gui_button(Rectangle::new(510.0, 150.0, 120.0, 50.0), "EDIT")
+        {
+            self.edit_selected_map(settings, transition);
+        }
+        if d.gui_button(Rectangle::new(510.0, 210.0, 120.0, 50.0), "NEW") {
+            self.edit_new_map(settings, transition);
+        }
+
+        // Continue button, only shown when there's a save to continue from
+        if quicksave_exists() && d.gui_button(Rectangle::new(300.0, 270.0, 200.0, 50.0), "CONTINUE")
+        {
+            continue_game(settings, transition);
+        }
+
+        // Quit button
+        if d.gui_button(Rectangle::new(300.0, 330.0, 200.0, 50.0), "QUIT") {
+            *transition = Some(Transition::Switch(State::Quit));
+        }
 
-    // Press Enter or Space to continue
-    if rl.is_key_pressed(KeyboardKey::KEY_ENTER) || rl.is_key_pressed(KeyboardKey::KEY_SPACE) {
-        // Set state to Game and create new game
-        match Game::new() {
-            Ok(game) => *next_state = Some(State::Game(Box::new(game))),
+        // Why the last game failed to start, first lines only to fit under the buttons
+        if let Some(error) = &self.error {
+            for (index, line) in error.lines().take(4).enumerate() {
+                d.draw_text(line, 10, 390 + index as i32 * 14, 12, Color::RED);
+            }
+        }
+    }
+
+    // Select previous map
+    fn previous_map(&mut self) {
+        self.error = None;
+
+        if !self.maps.is_empty() {
+            self.selected_map = (self.selected_map + self.maps.len() - 1) % self.maps.len();
+        }
+    }
+
+    // Select next map
+    fn next_map(&mut self) {
+        self.error = None;
+
+        if !self.maps.is_empty() {
+            self.selected_map = (self.selected_map + 1) % self.maps.len();
+        }
+    }
+
+    // Start a game on the selected map
+    fn start_single_map(&mut self, settings: &Settings, transition: &mut Option<Transition>) {
+        if let Some(map) = self.maps.get(self.selected_map) {
+            let mode = GameMode::SingleMap(map.clone());
+
+            self.start_game(settings, mode, transition);
+        }
+    }
+
+    // Start a campaign from the first map
+    fn start_campaign(&mut self, settings: &Settings, transition: &mut Option<Transition>) {
+        if !self.maps.is_empty() {
+            let mode = GameMode::Campaign {
+                maps: self.maps.maps.clone(),
+                current: 0,
+            };
+
+            self.start_game(settings, mode, transition);
+        }
+    }
+
+    // Set state to Editor with the selected map
+    // Stays on the title screen showing the error if the map can't be loaded
+    fn edit_selected_map(&mut self, settings: &Settings, transition: &mut Option<Transition>) {
+        let Some(map) = self.maps.get(self.selected_map) else {
+            return;
+        };
+
+        match Editor::open(settings, &map.filename) {
+            Ok(editor) => {
+                self.error = None;
+                *transition = Some(Transition::Switch(State::Editor(Box::new(editor))));
+            }
             Err(e) => {
-                eprintln!("Failed to create game: {e}");
-                *next_state = Some(State::Quit);
+                eprintln!("Failed to open editor: {e}");
+                self.error = Some(format!("Failed to open editor: {e}"));
             }
         }
     }
-}
 
-// Render
-pub fn render(rl: &mut RaylibHandle, thread: &RaylibThread, next_state: &mut Option<State>) {
-    // Begin drawing frame
-    let mut d = rl.begin_drawing(thread);
+    // Set state to Editor with a new native map, named after the maps already there
+    fn edit_new_map(&mut self, settings: &Settings, transition: &mut Option<Transition>) {
+        let name = (self.maps.len() + 1..)
+            .map(|number| format!("map_{number:02}"))
+            .find(|name| !self.maps.maps.iter().any(|map| map.name == *name))
+            .unwrap_or_default();
+        let filename = format!("{name}.{NATIVE_MAP_EXTENSION}");
 
-    // Clear frame
-    d.clear_background(Color::WHITE);
+        let editor = Editor::new_map(settings, &filename);
 
-    // Start button
-    if d.gui_button(Rectangle::new(300.0, 150.0, 200.0, 50.0), "START") {
-        // Set state to Game and create new game
-        match Game::new() {
-            Ok(game) => *next_state = Some(State::Game(Box::new(game))),
+        *transition = Some(Transition::Switch(State::Editor(Box::new(editor))));
+    }
+
+    // Set state to Game and create new game
+    // Stays on the title screen showing the error if the map can't be loaded
+    fn start_game(
+        &mut self,
+        settings: &Settings,
+        mode: GameMode,
+        transition: &mut Option<Transition>,
+    ) {
+        match Game::new(
+            settings,
+            mode,
+            PlayerStats::default(),
+            settings.seed_or_random(),
+        ) {
+            Ok(game) => {
+                self.error = None;
+                *transition = Some(Transition::Switch(State::Game(Box::new(game))));
+            }
             Err(e) => {
                 eprintln!("Failed to create game: {e}");
-                *next_state = Some(State::Quit);
+                self.error = Some(format!("Failed to create game: {e}"));
             }
         }
     }
+}
 
-    // Quit button
-    if d.gui_button(Rectangle::new(300.0, 250.0, 200.0, 50.0), "QUIT") {
-        *next_state = Some(State::Quit);
+// Set state to Game and load quick save
+fn continue_game(settings: &Settings, transition: &mut Option<Transition>) {
+    match Game::load(settings, QUICKSAVE_PATH) {
+        Ok(game) => *transition = Some(Transition::Switch(State::Game(Box::new(game)))),
+        Err(e) => eprintln!("Failed to load game: {e}"),
     }
 }
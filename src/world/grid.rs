@@ -1,26 +1,101 @@
 use crate::settings::*;
 use rapier3d::prelude::*;
-use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
 
 // Grid
-#[derive(Debug)]
+// Voxel volume of tiles, stored level by level along y
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Grid {
     pub width: usize,
     pub height: usize,
-    pub tiles: Vec<Vec<Tile>>,
+    // Number of levels stacked along y
+    pub levels: usize,
+    tiles: Vec<Tile>,
     pub tile_size: f32,
 }
 
 // Grid functions
 impl Grid {
-    // New grid
-    pub fn new(width: usize, height: usize, tiles: Vec<Vec<Tile>>) -> Self {
-        Self {
+    // New grid filled with air
+    pub fn new(width: usize, height: usize, levels: usize) -> Self {
+        let mut grid = Self {
             width,
             height,
-            tiles,
+            levels: 0,
+            tiles: Vec::new(),
             tile_size: 1.0,
+        };
+
+        grid.add_levels(levels);
+
+        grid
+    }
+
+    // All tiles in the grid
+    pub fn tiles(&self) -> impl Iterator<Item = &Tile> {
+        self.tiles.iter()
+    }
+
+    // Get tile at coordinates
+    pub fn get(&self, coord: GridCoord) -> Option<&Tile> {
+        self.index(coord).map(|index| &self.tiles[index])
+    }
+
+    // Set tile at its coordinates, adding levels above if needed
+    pub fn set(&mut self, tile: Tile) {
+        if tile.coord.y >= self.levels {
+            self.add_levels(tile.coord.y + 1 - self.levels);
+        }
+
+        match self.index(tile.coord) {
+            Some(index) => self.tiles[index] = tile,
+            None => panic!(
+                "Coordinates out of bounds: ({}, {}, {}) for grid {}x{}x{}",
+                tile.coord.x, tile.coord.y, tile.coord.z, self.width, self.levels, self.height
+            ),
+        }
+    }
+
+    // Place a tile where there's nothing solid yet
+    // Returns false if the coordinates are off the grid or already taken
+    pub fn place(&mut self, coord: GridCoord, kind: TileType) -> bool {
+        if coord.x >= self.width || coord.z >= self.height || self.is_solid(coord) {
+            return false;
+        }
+
+        self.set(Tile { kind, coord });
+
+        true
+    }
+
+    // Remove a tile, leaving air behind, and get what was there
+    pub fn remove(&mut self, coord: GridCoord) -> Option<TileType> {
+        let kind = self.get(coord)?.kind;
+
+        if matches!(kind, TileType::Air) {
+            return None;
         }
+
+        self.set(Tile {
+            kind: TileType::Air,
+            coord,
+        });
+
+        Some(kind)
+    }
+
+    // Check if there's a solid tile at coordinates
+    pub fn is_solid(&self, coord: GridCoord) -> bool {
+        self.get(coord)
+            .is_some_and(|tile| !matches!(tile.kind, TileType::Air))
+    }
+
+    // Height just above the highest solid tile in a column, 0 if the column is empty
+    pub fn surface_height(&self, x: usize, z: usize) -> usize {
+        (0..self.levels)
+            .rev()
+            .find(|&y| self.is_solid(GridCoord { x, y, z }))
+            .map_or(0, |y| y + 1)
     }
 
     // Fill area with tiles
@@ -49,30 +124,85 @@ impl Grid {
                     coord: tile_coord,
                 };
 
-                if DEBUG_MODE {
+                if debug_mode() {
+                    println!("📩 {tile:?}");
+                }
+
+                self.set(tile);
+            }
+        }
+    }
+
+    // Cover area with tiles
+    // Each column gets a tile on its own surface, unless it's already topped with the same kind
+    pub fn cover_area(&mut self, position: GridCoord, radius: isize, kind: TileType) {
+        for dz in -radius..=radius {
+            for dx in -radius..=radius {
+                let tile_x = position.x as isize + dx;
+                let tile_z = position.z as isize + dz;
+
+                // Bounds check
+                if tile_x < 0 || tile_x >= self.width as isize {
+                    continue;
+                }
+                if tile_z < 0 || tile_z >= self.height as isize {
+                    continue;
+                }
+
+                let (x, z) = (tile_x as usize, tile_z as usize);
+                let surface = self.surface_height(x, z);
+
+                // Overlapping areas don't stack
+                let topped = surface
+                    .checked_sub(1)
+                    .and_then(|y| self.get(GridCoord { x, y, z }))
+                    .is_some_and(|tile| tile.kind == kind);
+                if topped {
+                    continue;
+                }
+
+                let tile = Tile {
+                    kind,
+                    coord: GridCoord { x, y: surface, z },
+                };
+
+                if debug_mode() {
                     println!("📩 {tile:?}");
                 }
 
-                self.add_tile(tile);
+                self.set(tile);
             }
         }
     }
 
-    // Add tile
-    fn add_tile(&mut self, tile: Tile) {
-        if tile.coord.z < self.height && tile.coord.x < self.width {
-            self.tiles[tile.coord.z][tile.coord.x] = tile;
+    // Get index of coordinates in the tile list
+    fn index(&self, coord: GridCoord) -> Option<usize> {
+        if coord.x < self.width && coord.y < self.levels && coord.z < self.height {
+            Some((coord.y * self.height + coord.z) * self.width + coord.x)
         } else {
-            panic!(
-                "Coordinates out of bounds: ({}, {}) for grid {}x{}",
-                tile.coord.x, tile.coord.z, self.width, self.height
-            );
+            None
         }
     }
+
+    // Add levels of air on top of the grid
+    fn add_levels(&mut self, count: usize) {
+        for y in self.levels..self.levels + count {
+            for z in 0..self.height {
+                for x in 0..self.width {
+                    self.tiles.push(Tile {
+                        kind: TileType::Air,
+                        coord: GridCoord { x, y, z },
+                    });
+                }
+            }
+        }
+
+        self.levels += count;
+    }
 }
 
 // Grid coordinates
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GridCoord {
     pub x: usize,
     pub y: usize,
@@ -86,16 +216,8 @@ impl GridCoord {
         Self { x: 0, y: 0, z: 0 }
     }
 
-    // Grid coordinates to Raylib Vector3
-    pub fn to_raylib_vec3(&self, tile_size: f32) -> Vector3 {
-        Vector3::new(
-            self.x as f32 * tile_size,
-            self.y as f32 * tile_size,
-            self.z as f32 * tile_size,
-        )
-    }
-
     // Grid coordinates to Rapier3D Vector
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_rapier3d_vec(&self, tile_size: f32) -> Vector<f32> {
         vector![
             self.x as f32 * tile_size,
@@ -105,6 +227,7 @@ impl GridCoord {
     }
 
     // Grid coordinates to Rapier3D Vector but takes new coordinates
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_rapier3d_vec_new(
         &mut self,
         x: usize,
@@ -134,17 +257,46 @@ impl GridCoord {
 }
 
 // Tile
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Tile {
     pub kind: TileType,
     pub coord: GridCoord,
 }
 
 // Tile Types
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TileType {
     Air,
     Grass,
     Stone,
     Tree,
+    Exit,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cover_area_tops_each_column_without_replacing_blocks() {
+        let mut grid = Grid::new(3, 3, 1);
+        grid.fill_area(GridCoord { x: 1, y: 0, z: 1 }, 1, TileType::Grass);
+        grid.set(Tile {
+            kind: TileType::Stone,
+            coord: GridCoord { x: 2, y: 1, z: 1 },
+        });
+
+        grid.cover_area(GridCoord { x: 1, y: 1, z: 1 }, 1, TileType::Tree);
+        grid.cover_area(GridCoord { x: 2, y: 1, z: 1 }, 1, TileType::Tree);
+
+        // The raised column keeps its stone and gets its tree on top
+        let kind = |x, y, z| grid.get(GridCoord { x, y, z }).map(|tile| tile.kind);
+        assert_eq!(kind(2, 1, 1), Some(TileType::Stone));
+        assert_eq!(kind(2, 2, 1), Some(TileType::Tree));
+        assert_eq!(kind(0, 1, 0), Some(TileType::Tree));
+
+        // Overlapping covers don't stack
+        assert_eq!(grid.surface_height(1, 1), 2);
+        assert_eq!(grid.surface_height(2, 1), 3);
+    }
 }
@@ -1,101 +1,677 @@
+use crate::components::Rgba;
 use crate::settings::*;
+use crate::systems::objectives::*;
+use crate::systems::spawn::{SPAWN_MARGIN, can_spawn_randomly};
 use crate::world::grid::*;
-use std::path::PathBuf;
-use tiled::{LayerType, Loader, PropertyValue, TileLayer};
+use crate::world::native::*;
+use crate::world::patrol::*;
+use crate::world::spawns::*;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tiled::{
+    ChunkData, LayerType, Loader, Map, ObjectLayer, ObjectShape, Properties, PropertyValue,
+    TileLayer,
+};
 
-// Load Tiled map
-pub fn load_tiled_map(filename: &str) -> Result<Grid, tiled::Error> {
-    // Create loader
-    let mut loader = Loader::new();
+// Loaded map
+// Tile grid, the objectives declared in the map's properties, and what's placed in object layers
+pub struct LoadedMap {
+    pub grid: Grid,
+    pub objectives: Vec<Objective>,
+    pub patrol_routes: Vec<PatrolRoute>,
+    pub spawn_points: SpawnPoints,
+}
+
+// Load map from the maps directory, native or Tiled depending on its extension
+pub fn load_map(filename: &str) -> Result<LoadedMap, MapError> {
+    if is_native_map(filename) {
+        load_native_map(filename)
+    } else {
+        load_tiled_map(filename)
+    }
+}
 
+// Load Tiled map from the maps directory
+pub fn load_tiled_map(filename: &str) -> Result<LoadedMap, MapError> {
     // Get file path
     let file_path = PathBuf::from("assets").join("maps").join(filename);
 
+    read_tiled_map(&file_path, filename)
+}
+
+// Read Tiled map from a file
+// Every problem found is reported together rather than stopping at the first
+pub fn read_tiled_map(file_path: &Path, filename: &str) -> Result<LoadedMap, MapError> {
+    // Create loader
+    let mut loader = Loader::new();
+
     // Get map from file
-    let map = loader.load_tmx_map(file_path)?;
+    let map = loader.load_tmx_map(file_path).map_err(MapError::Tiled)?;
+
+    // Create new list to add problems found in the map
+    let mut issues = Vec::new();
+
+    // Get map bounds, covering every chunk of an infinite map
+    let bounds = map_bounds(&map);
 
     // Get map width and height
-    let width = map.width as usize;
-    let height = map.height as usize;
+    let width = bounds.width as usize;
+    let height = bounds.height as usize;
+
+    // Create empty grid, adding levels as tile layers are placed
+    let mut grid = Grid::new(width, height, 0);
 
-    // Create new list to add tiles
-    let mut tiles: Vec<Vec<Tile>> = vec![vec![]; width];
+    // Count tile layers to stack those without a Height property
+    let mut tile_layer_index = 0;
+
+    // Create new lists to add patrol routes and spawn points
+    let mut patrol_routes = Vec::new();
+    let mut spawn_points = SpawnPoints::default();
 
     // Go through each map layer
     for layer in map.layers() {
-        if let LayerType::Tiles(TileLayer::Finite(finite)) = layer.layer_type() {
+        // Object layers hold patrol routes and spawn points
+        if let LayerType::Objects(object_layer) = layer.layer_type() {
+            if debug_mode() {
+                println!("Processing object layer: {}", layer.name);
+            }
+
+            read_object_layer(
+                &map,
+                &bounds,
+                &layer.name,
+                &object_layer,
+                &mut patrol_routes,
+                &mut spawn_points,
+                &mut issues,
+            );
+        }
+
+        // Finite and infinite tile layers are both read within the map bounds
+        if let LayerType::Tiles(tile_layer) = layer.layer_type() {
             // Processing layer confirmation message
-            if DEBUG_MODE {
+            if debug_mode() {
                 println!("Processing tile layer: {}", layer.name);
             }
 
+            // Finite layers must cover the whole map
+            if let (Some(layer_width), Some(layer_height)) =
+                (tile_layer.width(), tile_layer.height())
+                && (layer_width != map.width || layer_height != map.height)
+            {
+                issues.push(MapIssue::LayerSizeMismatch {
+                    layer: layer.name.clone(),
+                    width: layer_width,
+                    height: layer_height,
+                    map_width: map.width,
+                    map_height: map.height,
+                });
+            }
+
+            // Get level from the layer's Height property, or stack layers in order
+            let level = match layer.properties.get("Height") {
+                Some(PropertyValue::IntValue(level)) if *level >= 0 => *level as usize,
+                None => tile_layer_index,
+                Some(_) => {
+                    issues.push(MapIssue::InvalidProperty {
+                        owner: format!("layer {:?}", layer.name),
+                        property: "Height".to_string(),
+                        expected: "a non-negative int",
+                    });
+                    tile_layer_index
+                }
+            };
+            tile_layer_index += 1;
+
             // Go through layer coordinates
-            for x in 0..finite.width() {
-                for y in 0..finite.height() {
+            for x in 0..bounds.width {
+                for y in 0..bounds.height {
                     // Flip Y since Tiled origin is top-left
-                    let flipped_z = (map.height - 1 - y) as usize;
+                    let flipped_z = (bounds.height - 1 - y) as usize;
+
+                    // Offset by the map origin to get Tiled tile coordinates
+                    let tiled_x = bounds.origin_x + x as i32;
+                    let tiled_y = bounds.origin_y + y as i32;
+
+                    // Skip empty tiles, leaving any tile another layer put there
+                    let Some(tile) = tile_layer.get_tile(tiled_x, tiled_y) else {
+                        continue;
+                    };
 
                     // Get tile property TileType and set our TileType
-                    if let Some(tile) = finite.get_tile(x as i32, y as i32) {
-                        let kind = if let Some(tile_def) = tile.get_tile() {
-                            match tile_def.properties.get("TileType") {
-                                Some(PropertyValue::StringValue(s)) => match s.as_str() {
-                                    "Grass" => TileType::Grass,
-                                    "Stone" => TileType::Stone,
-                                    _ => TileType::Air,
-                                },
-                                _ => TileType::Air,
+                    let tile_type = tile
+                        .get_tile()
+                        .and_then(|tile_def| tile_def.properties.get("TileType").cloned());
+
+                    let kind = match tile_type {
+                        Some(PropertyValue::StringValue(s)) => match s.as_str() {
+                            "Air" => TileType::Air,
+                            "Grass" => TileType::Grass,
+                            "Stone" => TileType::Stone,
+                            "Exit" => TileType::Exit,
+                            _ => {
+                                issues.push(MapIssue::UnknownTileType {
+                                    layer: layer.name.clone(),
+                                    x: tiled_x,
+                                    y: tiled_y,
+                                    tile_type: s,
+                                });
+                                continue;
                             }
-                        } else {
-                            TileType::Air
-                        };
-
-                        // Get height based on layer ID
-                        let height = layer.id() as usize - 1;
-
-                        // Set grid coordinated for tile
-                        let coord = GridCoord {
-                            x: x as usize,
-                            y: height,
-                            z: flipped_z,
-                        };
-
-                        // Create new tile based on TileType and coordinates
-                        let tile = Tile { kind, coord };
-
-                        // Tile confirmation message
-                        if DEBUG_MODE {
-                            println!("🟩 {tile:?}");
+                        },
+                        Some(_) => {
+                            issues.push(MapIssue::InvalidProperty {
+                                owner: format!(
+                                    "tile at ({tiled_x}, {tiled_y}) in layer {:?}",
+                                    layer.name
+                                ),
+                                property: "TileType".to_string(),
+                                expected: "a string",
+                            });
+                            continue;
                         }
+                        None => {
+                            issues.push(MapIssue::MissingProperty {
+                                layer: layer.name.clone(),
+                                x: tiled_x,
+                                y: tiled_y,
+                                property: "TileType",
+                            });
+                            continue;
+                        }
+                    };
+
+                    // Set grid coordinates for tile
+                    let coord = GridCoord {
+                        x: x as usize,
+                        y: level,
+                        z: flipped_z,
+                    };
+
+                    // Create new tile based on TileType and coordinates
+                    let tile = Tile { kind, coord };
+
+                    // Tile confirmation message
+                    if debug_mode() {
+                        println!("🟩 {tile:?}");
+                    }
+
+                    // Add tile to grid
+                    grid.set(tile);
+                }
+            }
+        }
+    }
+
+    // Get objectives from map properties
+    let objectives = objectives_from_properties(&map.properties, &mut issues);
+
+    // A ReachExit objective can't be completed without an exit
+    if objectives.contains(&Objective::ReachExit)
+        && !grid.tiles().any(|tile| matches!(tile.kind, TileType::Exit))
+    {
+        issues.push(MapIssue::NoExit);
+    }
+
+    check_random_spawns(&grid, &spawn_points, &objectives, &mut issues);
+
+    if !issues.is_empty() {
+        return Err(MapError::Invalid {
+            filename: filename.to_string(),
+            issues,
+        });
+    }
+
+    if debug_mode() {
+        println!("🏁 Map objectives: {objectives:?}");
+    }
+
+    if debug_mode() {
+        println!("🚩 Patrol routes: {patrol_routes:?}");
+        println!("📍 Spawn points: {spawn_points:?}");
+    }
+
+    // Return newly created grid, objectives, patrol routes, and spawn points
+    Ok(LoadedMap {
+        grid,
+        objectives,
+        patrol_routes,
+        spawn_points,
+    })
+}
+
+// Check a map that leaves some kinds of entity to be spawned randomly has room for them
+// Items are spawned randomly when fewer are placed than Collect objectives need
+pub fn check_random_spawns(
+    grid: &Grid,
+    spawn_points: &SpawnPoints,
+    objectives: &[Objective],
+    issues: &mut Vec<MapIssue>,
+) {
+    let items_needed = objectives.iter().any(|objective| {
+        matches!(objective, Objective::Collect { count } if *count as usize > spawn_points.items.len())
+    });
+
+    let needs_random_spawns = spawn_points.player.is_none()
+        || spawn_points.witches.is_empty()
+        || spawn_points.trees.is_empty()
+        || spawn_points.balls.is_empty()
+        || items_needed;
+
+    if needs_random_spawns && !can_spawn_randomly(grid) {
+        issues.push(MapIssue::TooSmallForRandomSpawns {
+            width: grid.width,
+            height: grid.height,
+        });
+    }
+}
+
+// Read patrol routes and spawn points from an object layer
+// Polylines and polygons are patrol routes, other objects are spawns named by class or name
+fn read_object_layer(
+    map: &Map,
+    bounds: &MapBounds,
+    layer_name: &str,
+    object_layer: &ObjectLayer,
+    patrol_routes: &mut Vec<PatrolRoute>,
+    spawn_points: &mut SpawnPoints,
+    issues: &mut Vec<MapIssue>,
+) {
+    for object in object_layer.objects() {
+        // Note a problem with this object
+        let mut invalid_spawn = |reason: &str| {
+            issues.push(MapIssue::InvalidSpawn {
+                layer: layer_name.to_string(),
+                object: object.name.clone(),
+                x: object.x,
+                y: object.y,
+                reason: reason.to_string(),
+            })
+        };
+
+        // Get object position, using the middle of rectangles and ellipses
+        let (x, y) = match &object.shape {
+            ObjectShape::Polyline { points } | ObjectShape::Polygon { points } => {
+                // Points are in pixels relative to the object
+                let points: Option<Vec<GridCoord>> = points
+                    .iter()
+                    .map(|(x, y)| pixel_to_coord(map, bounds, object.x + x, object.y + y))
+                    .collect();
+
+                match points {
+                    Some(points) if !points.is_empty() => patrol_routes.push(PatrolRoute {
+                        points,
+                        looped: matches!(object.shape, ObjectShape::Polygon { .. }),
+                    }),
+                    Some(_) => invalid_spawn("patrol route has no points"),
+                    None => invalid_spawn("patrol route goes off the map"),
+                }
+
+                continue;
+            }
+            ObjectShape::Rect { width, height } | ObjectShape::Ellipse { width, height } => {
+                (object.x + width / 2.0, object.y + height / 2.0)
+            }
+            _ => (object.x, object.y),
+        };
+
+        let Some(coord) = pixel_to_coord(map, bounds, x, y) else {
+            invalid_spawn("placed off the map");
+            continue;
+        };
 
-                        // Add tile to tile list
-                        tiles[x as usize].push(tile);
-                    } else {
-                        // If no tile insert a default Air tile
-                        let coord = GridCoord {
-                            x: x as usize,
-                            y: height,
-                            z: flipped_z,
-                        };
-
-                        let tile = Tile {
-                            kind: TileType::Air,
-                            coord,
-                        };
-
-                        // Tile confirmation message
-                        if DEBUG_MODE {
-                            println!("🟥 {tile:?}");
+        let kind = if object.user_type.is_empty() {
+            object.name.as_str()
+        } else {
+            object.user_type.as_str()
+        };
+
+        match kind {
+            "PlayerStart" if spawn_points.player.is_some() => {
+                invalid_spawn("more than one PlayerStart")
+            }
+            "PlayerStart" => spawn_points.player = Some(coord),
+            "Witch" => spawn_points.witches.push(coord),
+            "Tree" => {
+                let owner = format!("object {:?} in layer {layer_name:?}", object.name);
+                let properties = &object.properties;
+
+                spawn_points.trees.push(TreeSpawn {
+                    coord,
+                    leaf_width: float_property(properties, "LeafWidth", &owner, issues),
+                    leaf_height: float_property(properties, "LeafHeight", &owner, issues),
+                    trunk_height: float_property(properties, "TrunkHeight", &owner, issues),
+                    leaf_color: color_property(properties, "LeafColor", &owner, issues),
+                    trunk_color: color_property(properties, "TrunkColor", &owner, issues),
+                });
+            }
+            "Ball" => spawn_points.balls.push(coord),
+            "Item" => spawn_points.items.push(coord),
+            _ => invalid_spawn(&format!("unknown kind {kind:?}")),
+        }
+    }
+}
+
+// Map bounds in Tiled tile coordinates
+// Infinite maps can extend to negative coordinates, so the grid starts at the origin
+struct MapBounds {
+    origin_x: i32,
+    origin_y: i32,
+    width: u32,
+    height: u32,
+}
+
+// Get map bounds
+// Finite maps use their size, infinite maps the smallest area holding every tile in their chunks
+fn map_bounds(map: &Map) -> MapBounds {
+    if !map.infinite() {
+        return MapBounds {
+            origin_x: 0,
+            origin_y: 0,
+            width: map.width,
+            height: map.height,
+        };
+    }
+
+    let mut min = (i32::MAX, i32::MAX);
+    let mut max = (i32::MIN, i32::MIN);
+
+    for layer in map.layers() {
+        if let LayerType::Tiles(TileLayer::Infinite(infinite)) = layer.layer_type() {
+            for ((chunk_x, chunk_y), chunk) in infinite.chunks() {
+                for local_y in 0..ChunkData::HEIGHT as i32 {
+                    for local_x in 0..ChunkData::WIDTH as i32 {
+                        if chunk.get_tile(local_x, local_y).is_none() {
+                            continue;
                         }
 
-                        // Add tile to tile list
-                        tiles[x as usize].push(tile);
+                        // Chunk positions are in chunks, not tiles
+                        let x = chunk_x * ChunkData::WIDTH as i32 + local_x;
+                        let y = chunk_y * ChunkData::HEIGHT as i32 + local_y;
+
+                        min = (min.0.min(x), min.1.min(y));
+                        max = (max.0.max(x), max.1.max(y));
                     }
                 }
             }
         }
     }
 
-    // Return newly created grid
-    Ok(Grid::new(width, height, tiles))
+    // No tiles in any chunk
+    if min.0 > max.0 {
+        return MapBounds {
+            origin_x: 0,
+            origin_y: 0,
+            width: 0,
+            height: 0,
+        };
+    }
+
+    if debug_mode() {
+        println!("Infinite map spans tiles {min:?} to {max:?}");
+    }
+
+    MapBounds {
+        origin_x: min.0,
+        origin_y: min.1,
+        width: (max.0 - min.0 + 1) as u32,
+        height: (max.1 - min.1 + 1) as u32,
+    }
+}
+
+// Convert a position in pixels to the tile under it, if it's on the map
+fn pixel_to_coord(map: &Map, bounds: &MapBounds, x: f32, y: f32) -> Option<GridCoord> {
+    let tile_x = (x / map.tile_width as f32).floor() as i32 - bounds.origin_x;
+    let tile_y = (y / map.tile_height as f32).floor() as i32 - bounds.origin_y;
+
+    if tile_x < 0 || tile_y < 0 || tile_x >= bounds.width as i32 || tile_y >= bounds.height as i32 {
+        return None;
+    }
+
+    // Flip Y since Tiled origin is top-left
+    Some(GridCoord {
+        x: tile_x as usize,
+        y: 0,
+        z: (bounds.height - 1 - tile_y as u32) as usize,
+    })
+}
+
+// Read an optional positive float or int property
+fn float_property(
+    properties: &Properties,
+    name: &str,
+    owner: &str,
+    issues: &mut Vec<MapIssue>,
+) -> Option<f32> {
+    let value = match properties.get(name)? {
+        PropertyValue::FloatValue(value) => *value,
+        PropertyValue::IntValue(value) => *value as f32,
+        _ => f32::NAN,
+    };
+
+    if value > 0.0 {
+        Some(value)
+    } else {
+        issues.push(MapIssue::InvalidProperty {
+            owner: owner.to_string(),
+            property: name.to_string(),
+            expected: "a positive float or int",
+        });
+        None
+    }
+}
+
+// Read an optional color property
+fn color_property(
+    properties: &Properties,
+    name: &str,
+    owner: &str,
+    issues: &mut Vec<MapIssue>,
+) -> Option<Rgba> {
+    match properties.get(name)? {
+        PropertyValue::ColorValue(color) => {
+            Some(Rgba::new(color.red, color.green, color.blue, color.alpha))
+        }
+        _ => {
+            issues.push(MapIssue::InvalidProperty {
+                owner: owner.to_string(),
+                property: name.to_string(),
+                expected: "a color",
+            });
+            None
+        }
+    }
+}
+
+// Read objectives from map properties
+// SurviveSeconds (float or int), CollectItems (int), and ReachExit (bool)
+fn objectives_from_properties(
+    properties: &Properties,
+    issues: &mut Vec<MapIssue>,
+) -> Vec<Objective> {
+    let mut objectives = Vec::new();
+
+    if let Some(seconds) = float_property(properties, "SurviveSeconds", "map", issues) {
+        objectives.push(Objective::Survive { seconds });
+    }
+
+    match properties.get("CollectItems") {
+        Some(PropertyValue::IntValue(count)) if *count > 0 => {
+            objectives.push(Objective::Collect {
+                count: *count as u32,
+            });
+        }
+        Some(_) => issues.push(MapIssue::InvalidProperty {
+            owner: "map".to_string(),
+            property: "CollectItems".to_string(),
+            expected: "a positive int",
+        }),
+        None => {}
+    }
+
+    match properties.get("ReachExit") {
+        Some(PropertyValue::BoolValue(true)) => objectives.push(Objective::ReachExit),
+        Some(PropertyValue::BoolValue(false)) | None => {}
+        Some(_) => issues.push(MapIssue::InvalidProperty {
+            owner: "map".to_string(),
+            property: "ReachExit".to_string(),
+            expected: "a bool",
+        }),
+    }
+
+    objectives
+}
+
+// Map error
+#[derive(Debug)]
+pub enum MapError {
+    Tiled(tiled::Error),
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    Version(i64),
+    Migration {
+        version: u32,
+        reason: String,
+    },
+    Invalid {
+        filename: String,
+        issues: Vec<MapIssue>,
+    },
+}
+
+// Display map error
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapError::Tiled(e) => write!(f, "{e}"),
+            MapError::Io(e) => write!(f, "{e}"),
+            MapError::Parse(e) => write!(f, "{e}"),
+            MapError::Serialize(e) => write!(f, "{e}"),
+            MapError::Version(version) => write!(
+                f,
+                "map version {version} is not supported (expected 1 to {MAP_VERSION})"
+            ),
+            MapError::Migration { version, reason } => {
+                write!(f, "failed to migrate map from version {version}: {reason}")
+            }
+            MapError::Invalid { filename, issues } => {
+                write!(f, "{filename} has {} problem(s)", issues.len())?;
+
+                for issue in issues {
+                    write!(f, "\n  - {issue}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+// Map issue
+// A problem found in a map, with where in the map it was found
+// Tile and object positions are Tiled's, so they can be looked up in the editor
+#[derive(Debug)]
+pub enum MapIssue {
+    // Tile whose TileType isn't one we know
+    UnknownTileType {
+        layer: String,
+        x: i32,
+        y: i32,
+        tile_type: String,
+    },
+    // Tile missing a property it needs
+    MissingProperty {
+        layer: String,
+        x: i32,
+        y: i32,
+        property: &'static str,
+    },
+    // Property with the wrong type or value
+    InvalidProperty {
+        owner: String,
+        property: String,
+        expected: &'static str,
+    },
+    // Finite tile layer that isn't the size of the map
+    LayerSizeMismatch {
+        layer: String,
+        width: u32,
+        height: u32,
+        map_width: u32,
+        map_height: u32,
+    },
+    // Spawn point or patrol route that can't be used
+    InvalidSpawn {
+        layer: String,
+        object: String,
+        x: f32,
+        y: f32,
+        reason: String,
+    },
+    // ReachExit objective on a map with no Exit tiles
+    NoExit,
+    // Map too small to spawn entities that weren't placed in it
+    TooSmallForRandomSpawns {
+        width: usize,
+        height: usize,
+    },
+}
+
+// Display map issue
+impl fmt::Display for MapIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapIssue::UnknownTileType {
+                layer,
+                x,
+                y,
+                tile_type,
+            } => write!(
+                f,
+                "layer {layer:?} tile ({x}, {y}): unknown TileType {tile_type:?}"
+            ),
+            MapIssue::MissingProperty {
+                layer,
+                x,
+                y,
+                property,
+            } => write!(
+                f,
+                "layer {layer:?} tile ({x}, {y}): missing {property} property"
+            ),
+            MapIssue::InvalidProperty {
+                owner,
+                property,
+                expected,
+            } => write!(f, "{owner}: {property} should be {expected}"),
+            MapIssue::LayerSizeMismatch {
+                layer,
+                width,
+                height,
+                map_width,
+                map_height,
+            } => write!(
+                f,
+                "layer {layer:?} is {width}x{height} but the map is {map_width}x{map_height}"
+            ),
+            MapIssue::InvalidSpawn {
+                layer,
+                object,
+                x,
+                y,
+                reason,
+            } => write!(
+                f,
+                "layer {layer:?} object {object:?} at ({x}, {y}): {reason}"
+            ),
+            MapIssue::NoExit => write!(f, "map has a ReachExit objective but no Exit tiles"),
+            MapIssue::TooSmallForRandomSpawns { width, height } => write!(
+                f,
+                "map is {width}x{height}, too small to fit the entities spawned randomly; \
+                 make it bigger (at least {size}x{size}) or place every kind of entity",
+                size = SPAWN_MARGIN * 2 + 1
+            ),
+        }
+    }
 }
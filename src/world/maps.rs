@@ -0,0 +1,76 @@
+use crate::world::native::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// Directory maps are discovered in
+pub const MAPS_DIR: &str = "assets/maps";
+
+// Map info
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapInfo {
+    pub name: String,
+    pub filename: String,
+}
+
+// Map registry
+// Maps found on disk, sorted by filename which is also the campaign order
+#[derive(Clone, Debug, Default)]
+pub struct MapRegistry {
+    pub maps: Vec<MapInfo>,
+}
+
+// Functions for MapRegistry
+impl MapRegistry {
+    // Discover .tmx and native maps in a directory
+    pub fn discover(dir: impl AsRef<Path>) -> std::io::Result<Self> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .is_some_and(|ext| ext == "tmx" || ext == NATIVE_MAP_EXTENSION)
+            })
+            .collect();
+
+        paths.sort();
+
+        let maps = paths
+            .iter()
+            .filter_map(|path| {
+                Some(MapInfo {
+                    name: path.file_stem()?.to_string_lossy().into_owned(),
+                    filename: path.file_name()?.to_string_lossy().into_owned(),
+                })
+            })
+            .collect();
+
+        Ok(Self { maps })
+    }
+
+    // Discover maps in the default maps directory, or return an empty registry
+    pub fn discover_or_empty() -> Self {
+        match Self::discover(MAPS_DIR) {
+            Ok(registry) => registry,
+            Err(e) => {
+                eprintln!("Failed to discover maps in {MAPS_DIR}: {e}");
+                Self::default()
+            }
+        }
+    }
+
+    // Is registry empty
+    pub fn is_empty(&self) -> bool {
+        self.maps.is_empty()
+    }
+
+    // Number of maps
+    pub fn len(&self) -> usize {
+        self.maps.len()
+    }
+
+    // Get map by index
+    pub fn get(&self, index: usize) -> Option<&MapInfo> {
+        self.maps.get(index)
+    }
+}
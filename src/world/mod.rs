@@ -1,2 +1,8 @@
 pub mod grid;
 pub mod loader;
+pub mod maps;
+pub mod native;
+pub mod navigation;
+pub mod patrol;
+pub mod spawns;
+pub mod writer;
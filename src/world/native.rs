@@ -0,0 +1,468 @@
+use crate::settings::*;
+use crate::systems::objectives::*;
+use crate::world::grid::*;
+use crate::world::loader::*;
+use crate::world::maps::*;
+use crate::world::patrol::*;
+use crate::world::spawns::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// Native map format version
+// Older maps are migrated on load, so bump this and add a migration when the layout changes
+pub const MAP_VERSION: u32 = 2;
+
+// Extension of native map files
+pub const NATIVE_MAP_EXTENSION: &str = "toml";
+
+// Migration from one map version to the next, failing with the reason
+type Migration = fn(&mut toml::Table) -> Result<(), String>;
+
+// Migrations in version order, the first takes a version 1 map to version 2
+const MIGRATIONS: [Migration; 1] = [migrate_v1_grid];
+
+// Native map
+// Grid, placed entities and settings of a map in TOML, so maps don't need Tiled
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NativeMap {
+    pub version: u32,
+    #[serde(default)]
+    pub settings: MapSettings,
+    pub grid: GridLayers,
+    #[serde(default)]
+    pub spawn_points: SpawnPoints,
+    #[serde(default)]
+    pub patrol_routes: Vec<PatrolRoute>,
+}
+
+// Map settings
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MapSettings {
+    #[serde(default)]
+    pub objectives: Vec<Objective>,
+}
+
+// Grid layers
+// Each level is a list of rows with a character per tile, north row first as in Tiled
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GridLayers {
+    pub width: usize,
+    pub height: usize,
+    pub tile_size: f32,
+    pub levels: Vec<Vec<String>>,
+}
+
+// Functions for GridLayers
+impl GridLayers {
+    // Lay out a grid level by level
+    pub fn from_grid(grid: &Grid) -> Self {
+        let levels = (0..grid.levels)
+            .map(|y| {
+                (0..grid.height)
+                    .rev()
+                    .map(|z| {
+                        (0..grid.width)
+                            .map(|x| {
+                                grid.get(GridCoord { x, y, z })
+                                    .map_or('.', |tile| tile_char(tile.kind))
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            width: grid.width,
+            height: grid.height,
+            tile_size: grid.tile_size,
+            levels,
+        }
+    }
+
+    // Build the grid, noting a bad tile size, levels that aren't the size of the grid and unknown tiles
+    pub fn to_grid(&self, issues: &mut Vec<MapIssue>) -> Grid {
+        let mut grid = Grid::new(self.width, self.height, self.levels.len());
+        grid.tile_size = self.tile_size;
+
+        if !self.tile_size.is_finite() || self.tile_size <= 0.0 {
+            issues.push(MapIssue::InvalidProperty {
+                owner: "grid".to_string(),
+                property: "tile_size".to_string(),
+                expected: "a positive float",
+            });
+        }
+
+        for (level, rows) in self.levels.iter().enumerate() {
+            let layer = format!("level {level}");
+
+            // Report the first row that doesn't fit, or the row count if they all do
+            let row_width = rows
+                .iter()
+                .map(|row| row.chars().count())
+                .find(|width| *width != self.width)
+                .unwrap_or(self.width);
+            if rows.len() != self.height || row_width != self.width {
+                issues.push(MapIssue::LayerSizeMismatch {
+                    layer: layer.clone(),
+                    width: row_width as u32,
+                    height: rows.len() as u32,
+                    map_width: self.width as u32,
+                    map_height: self.height as u32,
+                });
+            }
+
+            for (row_index, row) in rows.iter().enumerate().take(self.height) {
+                for (x, tile) in row.chars().enumerate().take(self.width) {
+                    let Some(kind) = char_tile(tile) else {
+                        issues.push(MapIssue::UnknownTileType {
+                            layer: layer.clone(),
+                            x: x as i32,
+                            y: row_index as i32,
+                            tile_type: tile.to_string(),
+                        });
+                        continue;
+                    };
+
+                    // Rows run north to south
+                    let coord = GridCoord {
+                        x,
+                        y: level,
+                        z: self.height - 1 - row_index,
+                    };
+
+                    grid.set(Tile { kind, coord });
+                }
+            }
+        }
+
+        grid
+    }
+}
+
+// Functions for NativeMap
+impl NativeMap {
+    // Create native map from a loaded map, keeping everything in it
+    pub fn from_loaded(map: &LoadedMap) -> Self {
+        Self {
+            version: MAP_VERSION,
+            settings: MapSettings {
+                objectives: map.objectives.clone(),
+            },
+            grid: GridLayers::from_grid(&map.grid),
+            spawn_points: map.spawn_points.clone(),
+            patrol_routes: map.patrol_routes.clone(),
+        }
+    }
+
+    // Check the map and turn it into a loaded map
+    // Every problem found is reported together, as when loading Tiled maps
+    pub fn into_loaded(self, filename: &str) -> Result<LoadedMap, MapError> {
+        let mut issues = Vec::new();
+
+        let grid = self.grid.to_grid(&mut issues);
+        let objectives = self.settings.objectives;
+
+        // Spawns and routes must be on the grid
+        let on_grid = |coord: &GridCoord| coord.x < grid.width && coord.z < grid.height;
+        let mut off_grid = |layer: &str, object: &str, coord: GridCoord, reason: &str| {
+            issues.push(MapIssue::InvalidSpawn {
+                layer: layer.to_string(),
+                object: object.to_string(),
+                x: coord.x as f32,
+                y: coord.z as f32,
+                reason: reason.to_string(),
+            })
+        };
+
+        let spawns = &self.spawn_points;
+        let points = spawns
+            .player
+            .iter()
+            .map(|coord| ("PlayerStart", *coord))
+            .chain(spawns.witches.iter().map(|coord| ("Witch", *coord)))
+            .chain(spawns.trees.iter().map(|tree| ("Tree", tree.coord)))
+            .chain(spawns.balls.iter().map(|coord| ("Ball", *coord)))
+            .chain(spawns.items.iter().map(|coord| ("Item", *coord)));
+        for (object, coord) in points {
+            if !on_grid(&coord) {
+                off_grid("spawn_points", object, coord, "placed off the map");
+            }
+        }
+
+        for (index, route) in self.patrol_routes.iter().enumerate() {
+            let object = format!("Route {}", index + 1);
+
+            match route.points.first() {
+                None => off_grid(
+                    "patrol_routes",
+                    &object,
+                    GridCoord::zero(),
+                    "patrol route has no points",
+                ),
+                Some(first) if !route.points.iter().all(on_grid) => off_grid(
+                    "patrol_routes",
+                    &object,
+                    *first,
+                    "patrol route goes off the map",
+                ),
+                Some(_) => {}
+            }
+        }
+
+        // Tree sizes must be positive like in Tiled maps
+        for tree in &spawns.trees {
+            let sizes = [
+                ("leaf_width", tree.leaf_width),
+                ("leaf_height", tree.leaf_height),
+                ("trunk_height", tree.trunk_height),
+            ];
+            for (property, size) in sizes {
+                if size.is_some_and(|size| size.is_nan() || size <= 0.0) {
+                    issues.push(MapIssue::InvalidProperty {
+                        owner: format!("tree at ({}, {})", tree.coord.x, tree.coord.z),
+                        property: property.to_string(),
+                        expected: "a positive float",
+                    });
+                }
+            }
+        }
+
+        // A ReachExit objective can't be completed without an exit
+        if objectives.contains(&Objective::ReachExit)
+            && !grid.tiles().any(|tile| matches!(tile.kind, TileType::Exit))
+        {
+            issues.push(MapIssue::NoExit);
+        }
+
+        check_random_spawns(&grid, &self.spawn_points, &objectives, &mut issues);
+
+        if !issues.is_empty() {
+            return Err(MapError::Invalid {
+                filename: filename.to_string(),
+                issues,
+            });
+        }
+
+        Ok(LoadedMap {
+            grid,
+            objectives,
+            patrol_routes: self.patrol_routes,
+            spawn_points: self.spawn_points,
+        })
+    }
+}
+
+// Is file a native map
+pub fn is_native_map(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .is_some_and(|ext| ext == NATIVE_MAP_EXTENSION)
+}
+
+// Load native map from the maps directory, migrating older versions
+pub fn load_native_map(filename: &str) -> Result<LoadedMap, MapError> {
+    let file_path = PathBuf::from(MAPS_DIR).join(filename);
+    let text = std::fs::read_to_string(file_path).map_err(MapError::Io)?;
+
+    read_native_map(&text, filename)
+}
+
+// Read native map from its text, migrating older versions
+pub fn read_native_map(text: &str, filename: &str) -> Result<LoadedMap, MapError> {
+    let mut table: toml::Table = text.parse().map_err(MapError::Parse)?;
+    migrate(&mut table)?;
+
+    let map: NativeMap = table.try_into().map_err(MapError::Parse)?;
+
+    map.into_loaded(filename)
+}
+
+// Write native map to the maps directory
+pub fn write_native_map(filename: &str, map: &LoadedMap) -> Result<(), MapError> {
+    std::fs::create_dir_all(MAPS_DIR).map_err(MapError::Io)?;
+    let file_path = PathBuf::from(MAPS_DIR).join(filename);
+
+    let text = toml::to_string(&NativeMap::from_loaded(map)).map_err(MapError::Serialize)?;
+
+    std::fs::write(file_path, text).map_err(MapError::Io)
+}
+
+// Bring a map up to the current version, one version at a time
+fn migrate(table: &mut toml::Table) -> Result<(), MapError> {
+    let version = match table.get("version") {
+        Some(toml::Value::Integer(version)) => *version,
+        _ => 0,
+    };
+
+    // Negative versions and those too big for a u32 are rejected rather than wrapped
+    let version = match u32::try_from(version) {
+        Ok(version) if (1..=MAP_VERSION).contains(&version) => version,
+        _ => return Err(MapError::Version(version)),
+    };
+
+    for (from, migration) in (version..MAP_VERSION).zip(&MIGRATIONS[version as usize - 1..]) {
+        migration(table).map_err(|reason| MapError::Migration {
+            version: from,
+            reason,
+        })?;
+
+        if debug_mode() {
+            println!("🔁 Migrated map from version {from} to {}", from + 1);
+        }
+    }
+
+    table.insert(
+        "version".to_string(),
+        toml::Value::Integer(MAP_VERSION as i64),
+    );
+
+    Ok(())
+}
+
+// Version 1 stored the grid as save games do, a list of every tile with its coordinates
+fn migrate_v1_grid(table: &mut toml::Table) -> Result<(), String> {
+    #[derive(Deserialize)]
+    struct GridV1 {
+        width: usize,
+        height: usize,
+        levels: usize,
+        tiles: Vec<Tile>,
+        tile_size: f32,
+    }
+
+    let value = table.remove("grid").ok_or("missing grid")?;
+    let old: GridV1 = value
+        .try_into()
+        .map_err(|e: toml::de::Error| e.to_string())?;
+
+    let mut grid = Grid::new(old.width, old.height, old.levels);
+    grid.tile_size = old.tile_size;
+
+    for tile in old.tiles {
+        let coord = tile.coord;
+
+        if coord.x >= grid.width || coord.z >= grid.height || coord.y >= grid.levels {
+            return Err(format!(
+                "tile at ({}, {}, {}) is off the grid",
+                coord.x, coord.y, coord.z
+            ));
+        }
+
+        grid.set(tile);
+    }
+
+    let layers = toml::Value::try_from(GridLayers::from_grid(&grid)).map_err(|e| e.to_string())?;
+    table.insert("grid".to_string(), layers);
+
+    Ok(())
+}
+
+// Get character for a tile in grid layers
+fn tile_char(kind: TileType) -> char {
+    match kind {
+        TileType::Air => '.',
+        TileType::Grass => 'g',
+        TileType::Stone => 's',
+        TileType::Tree => 't',
+        TileType::Exit => 'e',
+    }
+}
+
+// Get tile for a character in grid layers
+fn char_tile(tile: char) -> Option<TileType> {
+    match tile {
+        '.' => Some(TileType::Air),
+        'g' => Some(TileType::Grass),
+        's' => Some(TileType::Stone),
+        't' => Some(TileType::Tree),
+        'e' => Some(TileType::Exit),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_map() -> LoadedMap {
+        let mut grid = Grid::new(8, 6, 2);
+        grid.fill_area(GridCoord { x: 3, y: 0, z: 3 }, 8, TileType::Grass);
+        grid.set(Tile {
+            kind: TileType::Exit,
+            coord: GridCoord { x: 7, y: 1, z: 0 },
+        });
+
+        LoadedMap {
+            grid,
+            objectives: vec![Objective::Collect { count: 1 }, Objective::ReachExit],
+            patrol_routes: vec![PatrolRoute {
+                points: vec![
+                    GridCoord { x: 1, y: 0, z: 1 },
+                    GridCoord { x: 5, y: 0, z: 4 },
+                ],
+                looped: true,
+            }],
+            spawn_points: SpawnPoints {
+                player: Some(GridCoord { x: 0, y: 0, z: 0 }),
+                witches: vec![GridCoord { x: 7, y: 0, z: 5 }],
+                trees: vec![TreeSpawn {
+                    coord: GridCoord { x: 4, y: 0, z: 2 },
+                    leaf_width: Some(3.0),
+                    leaf_height: None,
+                    trunk_height: None,
+                    leaf_color: None,
+                    trunk_color: None,
+                }],
+                balls: vec![GridCoord { x: 2, y: 0, z: 2 }],
+                items: vec![GridCoord { x: 3, y: 0, z: 5 }],
+            },
+        }
+    }
+
+    #[test]
+    fn native_map_round_trips() {
+        let map = test_map();
+        let text = toml::to_string(&NativeMap::from_loaded(&map)).unwrap();
+
+        let loaded = read_native_map(&text, "round_trip.toml").unwrap();
+
+        assert_eq!(
+            NativeMap::from_loaded(&loaded),
+            NativeMap::from_loaded(&map)
+        );
+    }
+
+    #[test]
+    fn versions_out_of_range_are_rejected() {
+        let native = NativeMap::from_loaded(&test_map());
+
+        // Two more than u32::MAX used to wrap around to version 1
+        for version in [0, -1, i64::from(MAP_VERSION) + 1, i64::from(u32::MAX) + 2] {
+            let mut table = toml::Table::try_from(&native).unwrap();
+            table.insert("version".to_string(), toml::Value::Integer(version));
+
+            let result = read_native_map(&table.to_string(), "version.toml");
+            assert!(
+                matches!(result, Err(MapError::Version(v)) if v == version),
+                "version {version} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn non_positive_tile_size_is_reported() {
+        let mut native = NativeMap::from_loaded(&test_map());
+        native.grid.tile_size = 0.0;
+
+        let text = toml::to_string(&native).unwrap();
+        let Err(MapError::Invalid { issues, .. }) = read_native_map(&text, "tiles.toml") else {
+            panic!("tile size of zero was accepted");
+        };
+
+        assert!(issues.iter().any(|issue| matches!(
+            issue,
+            MapIssue::InvalidProperty { property, .. } if property == "tile_size"
+        )));
+    }
+}
@@ -0,0 +1,310 @@
+use crate::world::grid::*;
+use pathfinding::prelude::astar;
+use rand::{rngs::StdRng, Rng};
+use rapier3d::prelude::*;
+
+// Cost of a straight and a diagonal step (scaled to keep costs as integers)
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+// Navigation grid
+#[derive(Debug)]
+pub struct NavGrid {
+    pub width: usize,
+    pub height: usize,
+    pub tile_size: f32,
+    blocked: Vec<bool>,
+    // Surface height of each column, witches can step down but can't climb
+    surfaces: Vec<usize>,
+}
+
+// Navigation grid functions
+impl NavGrid {
+    // Build navigation grid from tile grid
+    pub fn from_grid(grid: &Grid) -> Self {
+        let mut nav_grid = Self {
+            width: grid.width,
+            height: grid.height,
+            tile_size: grid.tile_size,
+            blocked: vec![false; grid.width * grid.height],
+            surfaces: vec![0; grid.width * grid.height],
+        };
+
+        for z in 0..grid.height {
+            for x in 0..grid.width {
+                nav_grid.update_column(grid, x, z);
+            }
+        }
+
+        nav_grid
+    }
+
+    // Update whether a column can be walked on and how high its surface is, after its tiles change
+    // Columns containing a tree, or with nothing to stand on, are blocked
+    pub fn update_column(&mut self, grid: &Grid, x: usize, z: usize) {
+        if !self.is_in_bounds(x, z) {
+            return;
+        }
+
+        let has_tree = (0..grid.levels).any(|y| {
+            grid.get(GridCoord { x, y, z })
+                .is_some_and(|tile| matches!(tile.kind, TileType::Tree))
+        });
+        let surface = grid.surface_height(x, z);
+
+        self.blocked[z * self.width + x] = has_tree || surface == 0;
+        self.surfaces[z * self.width + x] = surface;
+    }
+
+    // Check if a cell is on the navigation grid
+    fn is_in_bounds(&self, x: usize, z: usize) -> bool {
+        x < self.width && z < self.height
+    }
+
+    // Check if a cell can be walked through
+    pub fn is_walkable(&self, x: usize, z: usize) -> bool {
+        self.is_in_bounds(x, z) && !self.blocked[z * self.width + x]
+    }
+
+    // Pick a random walkable cell, if there are any
+    pub fn random_walkable_cell(&self, rng: &mut StdRng) -> Option<GridCoord> {
+        let walkable = self.blocked.iter().filter(|blocked| !**blocked).count();
+
+        if walkable == 0 {
+            return None;
+        }
+
+        let index = self
+            .blocked
+            .iter()
+            .enumerate()
+            .filter(|(_, blocked)| !**blocked)
+            .nth(rng.random_range(0..walkable))
+            .map(|(index, _)| index)?;
+
+        Some(GridCoord {
+            x: index % self.width,
+            y: 0,
+            z: index / self.width,
+        })
+    }
+
+    // Convert a Rapier3D Vector to the nearest cell on the navigation grid
+    pub fn cell_from_rapier3d_vec(&self, vector: Vector<f32>) -> GridCoord {
+        let x = (vector.x / self.tile_size).round().max(0.0) as usize;
+        let z = (vector.z / self.tile_size).round().max(0.0) as usize;
+
+        GridCoord {
+            x: x.min(self.width.saturating_sub(1)),
+            y: 0,
+            z: z.min(self.height.saturating_sub(1)),
+        }
+    }
+
+    // Find a path between two cells using A*
+    // Returned path excludes the start cell and ends at the goal cell
+    pub fn find_path(&self, start: GridCoord, goal: GridCoord) -> Option<Vec<GridCoord>> {
+        let start_node = (start.x, start.z);
+        let goal_node = (goal.x, goal.z);
+
+        let (nodes, _) = astar(
+            &start_node,
+            |&(x, z)| self.successors(x, z, goal_node),
+            |&(x, z)| octile_distance((x, z), goal_node),
+            |&node| node == goal_node,
+        )?;
+
+        Some(
+            nodes
+                .into_iter()
+                .skip(1)
+                .map(|(x, z)| GridCoord { x, y: start.y, z })
+                .collect(),
+        )
+    }
+
+    // Get walkable neighbours of a cell along with the cost to step to them
+    fn successors(&self, x: usize, z: usize, goal: (usize, usize)) -> Vec<((usize, usize), u32)> {
+        let mut neighbours = Vec::with_capacity(8);
+
+        // Goal is always enterable so a target hugging an obstacle can still be reached
+        // Other cells must be walkable and no higher than this one, as witches can't climb
+        let surface = self.surfaces[z * self.width + x];
+        let can_enter = |nx: usize, nz: usize| {
+            (nx, nz) == goal
+                || (self.is_walkable(nx, nz) && self.surfaces[nz * self.width + nx] <= surface)
+        };
+
+        for dz in -1isize..=1 {
+            for dx in -1isize..=1 {
+                if dx == 0 && dz == 0 {
+                    continue;
+                }
+
+                let nx = x as isize + dx;
+                let nz = z as isize + dz;
+
+                // Bounds check
+                if nx < 0 || nz < 0 {
+                    continue;
+                }
+
+                let (nx, nz) = (nx as usize, nz as usize);
+
+                if !can_enter(nx, nz) {
+                    continue;
+                }
+
+                // Diagonal steps can't cut the corner of a blocked cell
+                if dx != 0 && dz != 0 {
+                    if !can_enter(nx, z) || !can_enter(x, nz) {
+                        continue;
+                    }
+
+                    neighbours.push(((nx, nz), DIAGONAL_COST));
+                } else {
+                    neighbours.push(((nx, nz), STRAIGHT_COST));
+                }
+            }
+        }
+
+        neighbours
+    }
+}
+
+// Octile distance heuristic for 8-way movement
+fn octile_distance(a: (usize, usize), b: (usize, usize)) -> u32 {
+    let dx = a.0.abs_diff(b.0) as u32;
+    let dz = a.1.abs_diff(b.1) as u32;
+
+    STRAIGHT_COST * dx.max(dz) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Grass floor with stone columns standing on it
+    fn grid_with_stones(width: usize, height: usize, stones: &[(usize, usize)]) -> Grid {
+        let mut grid = Grid::new(width, height, 2);
+
+        for z in 0..height {
+            for x in 0..width {
+                grid.set(Tile {
+                    kind: TileType::Grass,
+                    coord: GridCoord { x, y: 0, z },
+                });
+            }
+        }
+
+        for &(x, z) in stones {
+            grid.set(Tile {
+                kind: TileType::Stone,
+                coord: GridCoord { x, y: 1, z },
+            });
+        }
+
+        grid
+    }
+
+    fn cell(x: usize, z: usize) -> GridCoord {
+        GridCoord { x, y: 0, z }
+    }
+
+    #[test]
+    fn open_diagonal_path_is_direct() {
+        let nav_grid = NavGrid::from_grid(&grid_with_stones(5, 5, &[]));
+
+        let path = nav_grid.find_path(cell(0, 0), cell(3, 3)).unwrap();
+
+        assert_eq!(path, vec![cell(1, 1), cell(2, 2), cell(3, 3)]);
+    }
+
+    #[test]
+    fn tree_and_empty_columns_are_blocked() {
+        let mut grid = grid_with_stones(3, 3, &[]);
+        grid.set(Tile {
+            kind: TileType::Tree,
+            coord: GridCoord { x: 1, y: 1, z: 1 },
+        });
+        grid.remove(cell(2, 2));
+
+        let nav_grid = NavGrid::from_grid(&grid);
+
+        assert!(nav_grid.is_walkable(0, 0));
+        assert!(!nav_grid.is_walkable(1, 1));
+        assert!(!nav_grid.is_walkable(2, 2));
+        assert!(!nav_grid.is_walkable(3, 0));
+    }
+
+    #[test]
+    fn stone_floor_is_walkable() {
+        let mut grid = Grid::new(3, 1, 1);
+        for x in 0..3 {
+            grid.set(Tile {
+                kind: TileType::Stone,
+                coord: GridCoord { x, y: 0, z: 0 },
+            });
+        }
+
+        let nav_grid = NavGrid::from_grid(&grid);
+
+        assert!((0..3).all(|x| nav_grid.is_walkable(x, 0)));
+        assert_eq!(
+            nav_grid.find_path(cell(0, 0), cell(2, 0)),
+            Some(vec![cell(1, 0), cell(2, 0)])
+        );
+    }
+
+    #[test]
+    fn placed_pillar_can_be_stepped_down_from_but_not_climbed() {
+        let mut grid = grid_with_stones(4, 1, &[]);
+        let mut nav_grid = NavGrid::from_grid(&grid);
+
+        // A grass pillar placed in the middle of a corridor cuts it
+        grid.set(Tile {
+            kind: TileType::Grass,
+            coord: GridCoord { x: 1, y: 1, z: 0 },
+        });
+        nav_grid.update_column(&grid, 1, 0);
+
+        assert_eq!(nav_grid.find_path(cell(0, 0), cell(3, 0)), None);
+        assert_eq!(
+            nav_grid.find_path(cell(1, 0), cell(3, 0)),
+            Some(vec![cell(2, 0), cell(3, 0)])
+        );
+
+        // Digging it out opens the corridor again
+        grid.remove(GridCoord { x: 1, y: 1, z: 0 });
+        nav_grid.update_column(&grid, 1, 0);
+
+        assert!(nav_grid.find_path(cell(0, 0), cell(3, 0)).is_some());
+    }
+
+    #[test]
+    fn diagonal_steps_do_not_cut_corners() {
+        // Stone beside the diagonal from (0, 0) to (1, 1)
+        let nav_grid = NavGrid::from_grid(&grid_with_stones(3, 3, &[(1, 0)]));
+
+        let path = nav_grid.find_path(cell(0, 0), cell(1, 1)).unwrap();
+
+        assert_eq!(path, vec![cell(0, 1), cell(1, 1)]);
+    }
+
+    #[test]
+    fn walled_off_goal_has_no_path() {
+        let wall: Vec<(usize, usize)> = (0..5).map(|z| (2, z)).collect();
+        let nav_grid = NavGrid::from_grid(&grid_with_stones(5, 5, &wall));
+
+        assert_eq!(nav_grid.find_path(cell(0, 0), cell(4, 4)), None);
+    }
+
+    #[test]
+    fn blocked_goal_can_still_be_reached() {
+        let nav_grid = NavGrid::from_grid(&grid_with_stones(3, 1, &[(2, 0)]));
+
+        let path = nav_grid.find_path(cell(0, 0), cell(2, 0)).unwrap();
+
+        assert_eq!(path, vec![cell(1, 0), cell(2, 0)]);
+    }
+}
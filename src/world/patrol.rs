@@ -0,0 +1,77 @@
+use crate::world::grid::*;
+use serde::{Deserialize, Serialize};
+
+// Patrol route
+// Authored as a polyline or polygon object in a Tiled object layer
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PatrolRoute {
+    pub points: Vec<GridCoord>,
+    // Polygons loop back to the first point, polylines walk back along the line
+    pub looped: bool,
+}
+
+// Patrol route functions
+impl PatrolRoute {
+    // Number of waypoints before the route repeats
+    pub fn cycle_len(&self) -> usize {
+        if self.looped || self.points.len() < 2 {
+            self.points.len()
+        } else {
+            self.points.len() * 2 - 2
+        }
+    }
+
+    // Get the point for a waypoint index, wrapping around the route
+    pub fn point(&self, waypoint: usize) -> Option<GridCoord> {
+        let cycle_len = self.cycle_len();
+
+        if cycle_len == 0 {
+            return None;
+        }
+
+        let index = waypoint % cycle_len;
+
+        // Walk back along a polyline once the last point is reached
+        let index = if index < self.points.len() {
+            index
+        } else {
+            cycle_len - index
+        };
+
+        self.points.get(index).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(len: usize, looped: bool) -> PatrolRoute {
+        PatrolRoute {
+            points: (0..len).map(|x| GridCoord { x, y: 0, z: 0 }).collect(),
+            looped,
+        }
+    }
+
+    fn xs(route: &PatrolRoute, waypoints: usize) -> Vec<usize> {
+        (0..waypoints)
+            .map(|waypoint| route.point(waypoint).unwrap().x)
+            .collect()
+    }
+
+    #[test]
+    fn looped_route_wraps_to_the_first_point() {
+        assert_eq!(xs(&route(3, true), 7), vec![0, 1, 2, 0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn open_route_walks_back_along_the_line() {
+        assert_eq!(xs(&route(3, false), 7), vec![0, 1, 2, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn short_routes_stay_put_or_have_no_points() {
+        assert_eq!(xs(&route(1, false), 3), vec![0, 0, 0]);
+        assert_eq!(route(0, true).point(0), None);
+    }
+}
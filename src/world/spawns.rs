@@ -0,0 +1,49 @@
+use crate::components::*;
+use crate::world::grid::*;
+use serde::{Deserialize, Serialize};
+
+// Spawn points
+// Entities placed in Tiled object layers; kinds with none placed are spawned randomly
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpawnPoints {
+    pub player: Option<GridCoord>,
+    pub witches: Vec<GridCoord>,
+    pub trees: Vec<TreeSpawn>,
+    pub balls: Vec<GridCoord>,
+    pub items: Vec<GridCoord>,
+}
+
+// Tree spawn
+// Tree properties left unset in the map are picked randomly
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TreeSpawn {
+    pub coord: GridCoord,
+    pub leaf_width: Option<f32>,
+    pub leaf_height: Option<f32>,
+    pub trunk_height: Option<f32>,
+    pub leaf_color: Option<Rgba>,
+    pub trunk_color: Option<Rgba>,
+}
+
+// Tree spawn functions
+impl TreeSpawn {
+    // Apply properties set in the map over a tree
+    pub fn apply(&self, tree: &mut Tree) {
+        if let Some(leaf_width) = self.leaf_width {
+            tree.leaf_width = leaf_width;
+        }
+        if let Some(leaf_height) = self.leaf_height {
+            tree.leaf_height = leaf_height;
+        }
+        if let Some(trunk_height) = self.trunk_height {
+            tree.trunk_height = trunk_height;
+        }
+        if let Some(leaf_color) = self.leaf_color {
+            tree.leaf_color = leaf_color;
+        }
+        if let Some(trunk_color) = self.trunk_color {
+            tree.trunk_color = trunk_color;
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use crate::components::Rgba;
+use crate::systems::objectives::*;
+use crate::world::grid::*;
+use crate::world::loader::*;
+use crate::world::maps::*;
+use crate::world::native::*;
+use crate::world::patrol::*;
+use crate::world::spawns::*;
+use std::fmt::{self, Write};
+use std::path::PathBuf;
+
+// Size of a tile in pixels in written maps
+const TILE_PIXELS: i32 = 32;
+
+// Block tiles in the written tileset, in tile ID order
+const BLOCK_TILES: [&str; 3] = ["Grass", "Stone", "Exit"];
+
+// Write map to the maps directory, native or Tiled depending on its extension
+pub fn write_map(filename: &str, map: &LoadedMap) -> Result<(), MapError> {
+    if is_native_map(filename) {
+        write_native_map(filename, map)
+    } else {
+        write_tiled_map(filename, map).map_err(MapError::Io)
+    }
+}
+
+// Write Tiled map
+// Saves a map to the maps directory in a layout load_tiled_map reads back, one tile layer per level
+pub fn write_tiled_map(filename: &str, map: &LoadedMap) -> std::io::Result<()> {
+    std::fs::create_dir_all(MAPS_DIR)?;
+    let file_path = PathBuf::from(MAPS_DIR).join(filename);
+
+    // Writing to a String only fails if formatting does
+    let xml = tiled_map_xml(map).map_err(std::io::Error::other)?;
+
+    std::fs::write(file_path, xml)
+}
+
+// Build the TMX document for a map
+fn tiled_map_xml(map: &LoadedMap) -> Result<String, fmt::Error> {
+    let grid = &map.grid;
+    let mut xml = String::new();
+    let mut next_id = 1;
+
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        xml,
+        r#"<map version="1.10" tiledversion="1.11.0" orientation="orthogonal" renderorder="right-down" width="{}" height="{}" tilewidth="{TILE_PIXELS}" tileheight="{TILE_PIXELS}" infinite="0">"#,
+        grid.width, grid.height
+    )?;
+
+    // Objectives
+    if !map.objectives.is_empty() {
+        writeln!(xml, " <properties>")?;
+        for objective in &map.objectives {
+            match objective {
+                Objective::Survive { seconds } => {
+                    property(&mut xml, "SurviveSeconds", "float", seconds)
+                }
+                Objective::Collect { count } => property(&mut xml, "CollectItems", "int", count),
+                Objective::ReachExit => property(&mut xml, "ReachExit", "bool", "true"),
+            }?;
+        }
+        writeln!(xml, " </properties>")?;
+    }
+
+    // Tileset with a tile for each kind of block
+    writeln!(
+        xml,
+        r#" <tileset firstgid="1" name="blocks" tilewidth="{TILE_PIXELS}" tileheight="{TILE_PIXELS}" tilecount="{}" columns="{}">"#,
+        BLOCK_TILES.len(),
+        BLOCK_TILES.len()
+    )?;
+    for (id, name) in BLOCK_TILES.iter().enumerate() {
+        writeln!(
+            xml,
+            r#"  <tile id="{id}"><properties><property name="TileType" value="{name}"/></properties></tile>"#
+        )?;
+    }
+    writeln!(xml, " </tileset>")?;
+
+    // Tile layer for each level with blocks in it
+    for level in 0..grid.levels {
+        let gids: Vec<u32> = (0..grid.height)
+            .flat_map(|y| (0..grid.width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                // Flip Y since Tiled origin is top-left
+                let coord = GridCoord {
+                    x,
+                    y: level,
+                    z: grid.height - 1 - y,
+                };
+                grid.get(coord).map_or(0, |tile| tile_gid(tile.kind))
+            })
+            .collect();
+
+        if gids.iter().all(|gid| *gid == 0) {
+            continue;
+        }
+
+        writeln!(
+            xml,
+            r#" <layer id="{next_id}" name="Level {level}" width="{}" height="{}">"#,
+            grid.width, grid.height
+        )?;
+        next_id += 1;
+
+        writeln!(xml, "  <properties>")?;
+        property(&mut xml, "Height", "int", level)?;
+        writeln!(xml, "  </properties>")?;
+
+        let rows: Vec<String> = gids
+            .chunks(grid.width)
+            .map(|row| row.iter().map(u32::to_string).collect::<Vec<_>>().join(","))
+            .collect();
+
+        writeln!(xml, r#"  <data encoding="csv">"#)?;
+        writeln!(xml, "{}", rows.join(",\n"))?;
+        writeln!(xml, "</data>")?;
+        writeln!(xml, " </layer>")?;
+    }
+
+    // Patrol routes
+    writeln!(xml, r#" <objectgroup id="{next_id}" name="Patrols">"#)?;
+    next_id += 1;
+    for (index, route) in map.patrol_routes.iter().enumerate() {
+        write_route(&mut xml, grid, index, route)?;
+    }
+    writeln!(xml, " </objectgroup>")?;
+
+    // Spawn points
+    writeln!(xml, r#" <objectgroup id="{next_id}" name="Spawns">"#)?;
+    let spawns = &map.spawn_points;
+    if let Some(coord) = spawns.player {
+        write_point(&mut xml, grid, "PlayerStart", coord)?;
+    }
+    for coord in &spawns.witches {
+        write_point(&mut xml, grid, "Witch", *coord)?;
+    }
+    for tree in &spawns.trees {
+        write_tree(&mut xml, grid, tree)?;
+    }
+    for coord in &spawns.balls {
+        write_point(&mut xml, grid, "Ball", *coord)?;
+    }
+    for coord in &spawns.items {
+        write_point(&mut xml, grid, "Item", *coord)?;
+    }
+    writeln!(xml, " </objectgroup>")?;
+
+    writeln!(xml, "</map>")?;
+
+    Ok(xml)
+}
+
+// Write a patrol route as a polyline, or a polygon if it loops
+fn write_route(xml: &mut String, grid: &Grid, index: usize, route: &PatrolRoute) -> fmt::Result {
+    let Some(first) = route.points.first() else {
+        return Ok(());
+    };
+
+    let (origin_x, origin_y) = coord_to_pixel(grid, *first);
+    let points: Vec<String> = route
+        .points
+        .iter()
+        .map(|point| {
+            let (x, y) = coord_to_pixel(grid, *point);
+            format!("{},{}", x - origin_x, y - origin_y)
+        })
+        .collect();
+    let shape = if route.looped { "polygon" } else { "polyline" };
+
+    writeln!(
+        xml,
+        r#"  <object name="Route {}" x="{origin_x}" y="{origin_y}">"#,
+        index + 1
+    )?;
+    writeln!(xml, r#"   <{shape} points="{}"/>"#, points.join(" "))?;
+    writeln!(xml, "  </object>")
+}
+
+// Write a spawn point
+fn write_point(xml: &mut String, grid: &Grid, name: &str, coord: GridCoord) -> fmt::Result {
+    let (x, y) = coord_to_pixel(grid, coord);
+
+    writeln!(
+        xml,
+        r#"  <object name="{name}" x="{x}" y="{y}"><point/></object>"#
+    )
+}
+
+// Write a tree spawn with the properties set on it
+fn write_tree(xml: &mut String, grid: &Grid, tree: &TreeSpawn) -> fmt::Result {
+    let (x, y) = coord_to_pixel(grid, tree.coord);
+
+    writeln!(xml, r#"  <object name="Tree" x="{x}" y="{y}">"#)?;
+    writeln!(xml, "   <properties>")?;
+
+    let floats = [
+        ("LeafWidth", tree.leaf_width),
+        ("LeafHeight", tree.leaf_height),
+        ("TrunkHeight", tree.trunk_height),
+    ];
+    for (name, value) in floats {
+        if let Some(value) = value {
+            property(xml, name, "float", value)?;
+        }
+    }
+
+    let colors = [
+        ("LeafColor", tree.leaf_color),
+        ("TrunkColor", tree.trunk_color),
+    ];
+    for (name, color) in colors {
+        if let Some(color) = color {
+            property(xml, name, "color", color_value(color))?;
+        }
+    }
+
+    writeln!(xml, "   </properties>")?;
+    writeln!(xml, "   <point/>")?;
+    writeln!(xml, "  </object>")
+}
+
+// Write a typed property
+fn property(xml: &mut String, name: &str, kind: &str, value: impl fmt::Display) -> fmt::Result {
+    writeln!(
+        xml,
+        r#"   <property name="{name}" type="{kind}" value="{value}"/>"#
+    )
+}
+
+// Get tile ID in the written tileset, or 0 for tiles that aren't blocks
+fn tile_gid(kind: TileType) -> u32 {
+    match kind {
+        TileType::Grass => 1,
+        TileType::Stone => 2,
+        TileType::Exit => 3,
+        _ => 0,
+    }
+}
+
+// Convert grid coordinates to the pixel position of the middle of their tile
+fn coord_to_pixel(grid: &Grid, coord: GridCoord) -> (i32, i32) {
+    // Flip Y since Tiled origin is top-left
+    let tile_y = grid.height - 1 - coord.z;
+
+    (
+        coord.x as i32 * TILE_PIXELS + TILE_PIXELS / 2,
+        tile_y as i32 * TILE_PIXELS + TILE_PIXELS / 2,
+    )
+}
+
+// Format a color as Tiled does, #AARRGGBB
+fn color_value(color: Rgba) -> String {
+    format!(
+        "#{:02x}{:02x}{:02x}{:02x}",
+        color.a, color.r, color.g, color.b
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiled_map_round_trips_through_the_loader() {
+        let mut grid = Grid::new(8, 6, 2);
+        grid.fill_area(GridCoord { x: 3, y: 0, z: 3 }, 8, TileType::Grass);
+        grid.set(Tile {
+            kind: TileType::Stone,
+            coord: GridCoord { x: 2, y: 1, z: 4 },
+        });
+        grid.set(Tile {
+            kind: TileType::Exit,
+            coord: GridCoord { x: 7, y: 1, z: 0 },
+        });
+
+        let map = LoadedMap {
+            grid,
+            objectives: vec![
+                Objective::Survive { seconds: 30.0 },
+                Objective::Collect { count: 2 },
+                Objective::ReachExit,
+            ],
+            patrol_routes: vec![
+                PatrolRoute {
+                    points: vec![
+                        GridCoord { x: 1, y: 0, z: 1 },
+                        GridCoord { x: 5, y: 0, z: 1 },
+                    ],
+                    looped: false,
+                },
+                PatrolRoute {
+                    points: vec![
+                        GridCoord { x: 1, y: 0, z: 4 },
+                        GridCoord { x: 6, y: 0, z: 4 },
+                        GridCoord { x: 6, y: 0, z: 2 },
+                    ],
+                    looped: true,
+                },
+            ],
+            spawn_points: SpawnPoints {
+                player: Some(GridCoord { x: 0, y: 0, z: 0 }),
+                witches: vec![GridCoord { x: 7, y: 0, z: 5 }],
+                trees: vec![TreeSpawn {
+                    coord: GridCoord { x: 4, y: 0, z: 2 },
+                    leaf_width: Some(3.0),
+                    leaf_height: None,
+                    trunk_height: Some(2.5),
+                    leaf_color: Some(Rgba::DARKGREEN),
+                    trunk_color: None,
+                }],
+                balls: vec![GridCoord { x: 2, y: 0, z: 2 }],
+                items: vec![
+                    GridCoord { x: 3, y: 0, z: 5 },
+                    GridCoord { x: 5, y: 0, z: 5 },
+                ],
+            },
+        };
+
+        let file_path = std::env::temp_dir().join(format!("round_trip_{}.tmx", std::process::id()));
+        std::fs::write(&file_path, tiled_map_xml(&map).unwrap()).unwrap();
+        let loaded = read_tiled_map(&file_path, "round_trip.tmx");
+        std::fs::remove_file(&file_path).unwrap();
+
+        assert_eq!(
+            NativeMap::from_loaded(&loaded.unwrap()),
+            NativeMap::from_loaded(&map)
+        );
+    }
+}